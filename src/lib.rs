@@ -7,15 +7,28 @@
 //! https://github.com/SimonSapin/rust-forest
 //! https://github.com/RazrFalcon/rctree/blob/master/src/lib.rs
 
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod macros;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod strategy;
+
+pub use strategy::MatchStrategy;
 
 /// Mutable reference.
-type Link<'a, T, U> = Rc<RefCell<Node<'a, T, U>>>;
+type Link<'a, T, U, K> = Rc<RefCell<Node<'a, T, U, K>>>;
 /// Weak mutable reference.
-type WeakLink<'a, T, U> = Weak<RefCell<Node<'a, T, U>>>;
+type WeakLink<'a, T, U, K> = Weak<RefCell<Node<'a, T, U, K>>>;
 /// Mutable reference to an hash map.
-type HashLink<'a, T, U> = Rc<RefCell<std::collections::HashMap<&'a str, WeakLink<'a, T, U>>>>;
+type HashLink<'a, T, U, K> = Rc<RefCell<std::collections::HashMap<K, WeakLink<'a, T, U, K>>>>;
+/// Exported fields of a node by name, each formatted as a quoted string or `null`.
+type Fields = [(&'static str, String); 4];
+
+/// Counter handing out the order in which nodes are created.
+static CREATED_ORDER: AtomicUsize = AtomicUsize::new(0);
 
 /// Return value if `Some`, else return `None`.
 #[macro_export]
@@ -29,26 +42,181 @@ macro_rules! try_opt {
 }
 
 /// Operator.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Op {
     Equal,
     Greater,
     GreaterEqual,
     Less,
     LessEqual,
+    NotEqual,
+    /// Catch-all child, moved to only when no other child matches.
+    Default,
+}
+
+impl Op {
+    /// Returns the name of the operator, e.g. `"Equal"`.
+    fn name(&self) -> &'static str {
+        match self {
+            Op::Equal => "Equal",
+            Op::Greater => "Greater",
+            Op::GreaterEqual => "GreaterEqual",
+            Op::Less => "Less",
+            Op::LessEqual => "LessEqual",
+            Op::NotEqual => "NotEqual",
+            Op::Default => "Default",
+        }
+    }
+
+    /// Returns the symbol of a comparison operator, `None` for `Default`.
+    fn symbol(&self) -> Option<&'static str> {
+        match self {
+            Op::Equal => Some("=="),
+            Op::Greater => Some(">"),
+            Op::GreaterEqual => Some(">="),
+            Op::Less => Some("<"),
+            Op::LessEqual => Some("<="),
+            Op::NotEqual => Some("!="),
+            Op::Default => None,
+        }
+    }
+}
+
+/// Operator given to `Traverse::traverse` deciding which child to move to.
+///
+/// The comparison variants compare the given decision against the decision of each
+/// child, e.g. `Greater` matches a child if the given decision is greater than its
+/// decision. The aggregate variants ignore the given decision and select the child
+/// with the largest (`Max`), smallest (`Min`) or middle (`Median`) decision.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PartialOp {
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Median,
+    Max,
+    Min,
+}
+
+impl PartialOp {
+    /// Returns the name of the operator, e.g. `"Equal"`.
+    fn name(&self) -> &'static str {
+        match self {
+            PartialOp::Equal => "Equal",
+            PartialOp::Greater => "Greater",
+            PartialOp::GreaterEqual => "GreaterEqual",
+            PartialOp::Less => "Less",
+            PartialOp::LessEqual => "LessEqual",
+            PartialOp::Median => "Median",
+            PartialOp::Max => "Max",
+            PartialOp::Min => "Min",
+        }
+    }
+
+    /// Returns true if the operator is one of the aggregate variants.
+    fn is_aggregate(&self) -> bool {
+        matches!(self, PartialOp::Median | PartialOp::Max | PartialOp::Min)
+    }
+}
+
+/// Error returned when a `Node` can't be appended.
+#[derive(Debug, Eq, PartialEq)]
+pub enum AppendError<K> {
+    /// A `Node` with the same id already exist in the tree.
+    DuplicateId(K),
+    /// The parent already has a default child.
+    DuplicateDefault(K),
+}
+
+impl<K: std::fmt::Debug> std::fmt::Display for AppendError<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AppendError::DuplicateId(id) => write!(f, "a node with the id {:?} already exist", id),
+            AppendError::DuplicateDefault(id) => {
+                write!(f, "the node {:?} already has a default child", id)
+            }
+        }
+    }
+}
+
+impl<K: std::fmt::Debug> std::error::Error for AppendError<K> {}
+
+/// Error returned when a subtree can't be attached to a tree.
+#[derive(Debug, Eq, PartialEq)]
+pub enum AttachError<K> {
+    /// A `Node` in the subtree has the same id as one that already exist in the tree.
+    DuplicateId(K),
+    /// The subtree contains the `Node` it would be attached to.
+    WouldCycle(K),
+    /// The subtree is a default child and the `Node` already has one.
+    DuplicateDefault(K),
+}
+
+impl<K: std::fmt::Debug> std::fmt::Display for AttachError<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AttachError::DuplicateId(id) => write!(f, "a node with the id {:?} already exist", id),
+            AttachError::WouldCycle(id) => {
+                write!(f, "the node {:?} can't be attached below itself", id)
+            }
+            AttachError::DuplicateDefault(id) => {
+                write!(f, "the node {:?} already has a default child", id)
+            }
+        }
+    }
+}
+
+impl<K: std::fmt::Debug> std::error::Error for AttachError<K> {}
+
+/// Error returned when a subtree can't be cloned into a tree.
+#[derive(Debug, Eq, PartialEq)]
+pub enum MergeError<K> {
+    /// There is no `Node` with the id of the template.
+    MissingTemplate(K),
+    /// A mapped id collides with an id in the tree, or with another mapped id.
+    DuplicateId(K),
+    /// The template is a default child and the `Node` already has one.
+    DuplicateDefault(K),
+}
+
+impl<K: std::fmt::Debug> std::fmt::Display for MergeError<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MergeError::MissingTemplate(id) => write!(f, "there is no node with the id {:?}", id),
+            MergeError::DuplicateId(id) => write!(f, "a node with the id {:?} already exist", id),
+            MergeError::DuplicateDefault(id) => {
+                write!(f, "the node {:?} already has a default child", id)
+            }
+        }
+    }
+}
+
+impl<K: std::fmt::Debug> std::error::Error for MergeError<K> {}
+
+/// Leaves reached and never reached by a set of inputs, returned by `DT::coverage`.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct CoverageReport<K> {
+    /// Ids of the leaves reached by at least one input, in depth-first order.
+    pub reached: Vec<K>,
+    /// Ids of the leaves no input reached, in depth-first order.
+    pub unreached: Vec<K>,
 }
 
 /// Decision Tree
 ///
-/// Generic implementation that takes in a unique id `&str` that is implemented as
-/// a hash map. It decides what route to take base on the decision that has `PartialEq` +
-/// `PartialOrd` + `Copy` derived. By using the `Traverse` struct, it is possible to move
-/// along the tree.
+/// Generic implementation that takes in a unique id, `&str` by default or any key `K`
+/// that is `Eq` + `Hash` + `Clone`, that is implemented as a hash map. Ids are looked
+/// up by any borrowed form of the key, like `HashMap`. It decides what route to take
+/// base on the decision that has `PartialEq` + `PartialOrd` + `Copy` derived. By using
+/// the `Traverse` struct, it is possible to move along the tree.
 ///
 /// # Examples
 ///
 /// ```
-/// use cdt::{DT, Traverse, Op};
+/// use cdt::{DT, Traverse, Op, PartialOp};
 ///
 /// // Initialize a new decision tree by creating a root that
 /// // has by default the id "root"
@@ -69,78 +237,92 @@ pub enum Op {
 ///
 /// // apple because it is the first one that are false
 /// // The decision goes from left to right (top to bottom)
-/// assert!(travel.traverse(&false).unwrap().decision().unwrap() == false);
+/// assert!(travel.traverse(&false, PartialOp::Equal).unwrap().decision().unwrap() == false);
 ///
 /// // The first one of apple's children that are true
-/// assert!(travel.traverse(&true).unwrap().decision().unwrap() == true);
+/// assert!(travel.traverse(&true, PartialOp::Equal).unwrap().decision().unwrap() == true);
 /// ```
-pub struct DT<'a, T, U>(Link<'a, T, U>)
+pub struct DT<'a, T, U, K = &'a str>(Link<'a, T, U, K>)
 where
-    U: PartialEq + PartialOrd + Copy;
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone;
 
 #[derive(std::fmt::Debug)]
-struct Node<'a, T, U>
+struct Node<'a, T, U, K>
 where
     U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
 {
-    id: &'a str,
+    id: K,
+    lifetime: std::marker::PhantomData<&'a ()>,
     op: Option<Op>,
-    children: Vec<Link<'a, T, U>>,
-    latest_parent: Option<WeakLink<'a, T, U>>,
-    latest_child: Option<Link<'a, T, U>>,
+    children: Vec<Link<'a, T, U, K>>,
+    latest_parent: Option<WeakLink<'a, T, U, K>>,
+    latest_child: Option<Link<'a, T, U, K>>,
     decision: Option<U>,
     data: Option<T>,
-    hash: HashLink<'a, T, U>,
+    hash: HashLink<'a, T, U, K>,
+    created_order: usize,
+    negate: bool,
+    weight: Option<f64>,
+    priority: i32,
 }
 
 /// Cloning a 'Node' only increments a reference count. It does not copy the data.
-impl<'a, T, U> Clone for DT<'a, T, U>
+impl<'a, T, U, K> Clone for DT<'a, T, U, K>
 where
     U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
 {
     fn clone(&self) -> Self {
         DT(Rc::clone(&self.0))
     }
 }
 
-impl<'a, T, U> PartialEq for DT<'a, T, U>
+impl<'a, T, U, K> PartialEq for DT<'a, T, U, K>
 where
     U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
 {
-    fn eq(&self, other: &DT<'a, T, U>) -> bool {
+    fn eq(&self, other: &DT<'a, T, U, K>) -> bool {
         Rc::ptr_eq(&self.0, &other.0)
     }
 }
 
-// If T has trait debug
-impl<'a, T, U> std::fmt::Debug for DT<'a, T, U>
+// If T has trait debug. The id is rendered with its own `Debug`, so a `&str` id is quoted
+impl<'a, T, U, K> std::fmt::Debug for DT<'a, T, U, K>
 where
     T: std::fmt::Debug,
     U: PartialEq + PartialOrd + Copy + std::fmt::Debug,
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let self_borrow = &self.0.borrow();
-        f.debug_tuple(&self_borrow.id)
+        f.debug_tuple(&format!("{:?}", self_borrow.id))
             .field(&self_borrow.data)
             .field(&self_borrow.decision)
             .finish()
     }
 }
 
-impl<'a, T, U> std::fmt::Display for DT<'a, T, U>
+/// Renders the subtree with one `id: data (decision)` line per `Node`, where children
+/// are indented by two spaces per depth.
+impl<'a, T, U, K> std::fmt::Display for DT<'a, T, U, K>
 where
     T: std::fmt::Display,
-    U: PartialEq + PartialOrd + Copy,
+    U: PartialEq + PartialOrd + Copy + std::fmt::Display,
+    K: Eq + std::hash::Hash + Clone + std::fmt::Display,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        std::fmt::Display::fmt(&self, f)
+        write!(f, "{}", self.to_indented_lines().join("\n"))
     }
 }
 
-impl<'a, T, U> DT<'a, T, U>
+impl<'a, T, U, K> DT<'a, T, U, K>
 where
     T: Copy,
     U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
 {
     /// Returns the content inside the `Node`.
     ///
@@ -154,40 +336,433 @@ where
     }
 }
 
+impl<'a, T, U, K> DT<'a, T, U, K>
+where
+    T: Clone,
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    /// Returns a deep copy of the subtree, with its own hash map.
+    ///
+    /// Unlike `clone`, which only increments a reference count, every `Node` is copied
+    /// and the copy of this `Node` becomes the root of the new tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn deep_clone(&self) -> DT<'a, T, U, K> {
+        let hash = Rc::new(RefCell::new(std::collections::HashMap::new()));
+        self.clone_into_tree(&hash, &|id| id)
+    }
+
+    /// Returns a deep copy of the subtree with every id rewritten by `id_map`, with its
+    /// own hash map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id_map` maps two ids to the same id, or if any `Node` in the subtree is
+    /// currently mutably borrowed.
+    pub fn clone_with_ids<F>(&self, id_map: F) -> DT<'a, T, U, K>
+    where
+        F: Fn(K) -> K,
+    {
+        let mut mapped = std::collections::HashSet::new();
+        for node in self.iter_dfs() {
+            assert!(
+                mapped.insert(id_map(node.id())),
+                "Not allowed to map two ids to the same id."
+            );
+        }
+        let hash = Rc::new(RefCell::new(std::collections::HashMap::new()));
+        self.clone_into_tree(&hash, &id_map)
+    }
+
+    /// Deep-clone the subtree rooted at `template_id` and append the copy as a child of
+    /// this `Node`, with every id rewritten by `id_map`.
+    ///
+    /// Returns the copy, or a `MergeError` if the template doesn't exist, any mapped id
+    /// collides, or the template is a default child and this `Node` already has one. The
+    /// tree is left unchanged on failure.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any affected `Node` is currently mutably borrowed.
+    pub fn graft_clone_of(
+        &mut self,
+        template_id: K,
+        id_map: impl Fn(K) -> K,
+    ) -> Result<DT<'a, T, U, K>, MergeError<K>> {
+        let template = self
+            .find(&template_id)
+            .ok_or(MergeError::MissingTemplate(template_id))?;
+        let mut mapped = std::collections::HashSet::new();
+        for node in template.iter_dfs() {
+            let id = id_map(node.id());
+            if self.contains(&id) || !mapped.insert(id.clone()) {
+                return Err(MergeError::DuplicateId(id));
+            }
+        }
+        if template.0.borrow().op == Some(Op::Default) && self.default_child().is_some() {
+            return Err(MergeError::DuplicateDefault(self.id()));
+        }
+
+        let hash = self.0.borrow().hash.clone();
+        let copy = template.clone_into_tree(&hash, &id_map);
+        copy.0.borrow_mut().latest_parent = Some(Rc::downgrade(&self.0));
+        let mut self_borrow = self.0.borrow_mut();
+        self_borrow.latest_child = Some(copy.0.clone());
+        self_borrow.children.push(copy.0.clone());
+        Ok(copy)
+    }
+
+    /// Copy the subtree with its ids rewritten by `id_map`, registering every copied
+    /// `Node` in `hash`.
+    ///
+    /// The copies get fresh insertion indices that keep the relative order of the
+    /// originals.
+    fn clone_into_tree<F>(&self, hash: &HashLink<'a, T, U, K>, id_map: &F) -> DT<'a, T, U, K>
+    where
+        F: Fn(K) -> K,
+    {
+        let copy = self.copy_nodes(hash, id_map);
+        // Both subtrees have the same shape, so depth-first order pairs every original
+        // with its copy
+        let mut pairs: Vec<(usize, DT<'a, T, U, K>)> = self
+            .iter_dfs()
+            .map(|node| node.insertion_index())
+            .zip(copy.iter_dfs())
+            .collect();
+        pairs.sort_by_key(|(index, _)| *index);
+        for (_, node) in pairs {
+            node.0.borrow_mut().created_order = CREATED_ORDER.fetch_add(1, Ordering::Relaxed);
+        }
+        copy
+    }
+
+    /// Recursively copy the subtree for `clone_into_tree`.
+    fn copy_nodes<F>(&self, hash: &HashLink<'a, T, U, K>, id_map: &F) -> DT<'a, T, U, K>
+    where
+        F: Fn(K) -> K,
+    {
+        let self_borrow = self.0.borrow();
+        let id = id_map(self_borrow.id.clone());
+        let copy = DT::new(
+            id.clone(),
+            self_borrow.op.clone(),
+            self_borrow.data.clone(),
+            self_borrow.decision,
+            hash.clone(),
+        );
+        hash.borrow_mut().insert(id, Rc::downgrade(&copy.0));
+        {
+            let mut copy_borrow = copy.0.borrow_mut();
+            copy_borrow.negate = self_borrow.negate;
+            copy_borrow.weight = self_borrow.weight;
+            copy_borrow.priority = self_borrow.priority;
+        }
+        let latest_child = self_borrow.latest_child.clone();
+        drop(self_borrow);
+
+        for child in self.children() {
+            let is_latest = latest_child
+                .as_ref()
+                .is_some_and(|latest| Rc::ptr_eq(latest, &child.0));
+            let child_copy = child.copy_nodes(hash, id_map);
+            child_copy.0.borrow_mut().latest_parent = Some(Rc::downgrade(&copy.0));
+
+            let mut copy_borrow = copy.0.borrow_mut();
+            if is_latest {
+                copy_borrow.latest_child = Some(child_copy.0.clone());
+            }
+            copy_borrow.children.push(child_copy.0);
+        }
+        copy
+    }
+}
+
+impl<'a, T, U, K> DT<'a, T, U, K>
+where
+    T: std::fmt::Display,
+    U: PartialEq + PartialOrd + Copy + std::fmt::Display,
+    K: Eq + std::hash::Hash + Clone + std::fmt::Display,
+{
+    /// Returns the subtree as compact JSON.
+    ///
+    /// Every `Node` is written as an object with its `id`, `op`, `decision`, `data`
+    /// and `children`. Decisions and data are written as strings using `Display`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out, None, 0);
+        out
+    }
+
+    /// Returns the subtree as human-readable JSON, indented by `indent` spaces per level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn to_pretty_json(&self, indent: usize) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out, Some(indent), 0);
+        out
+    }
+
+    /// Returns one line per `Node` in the subtree in depth-first order, formatted as
+    /// `id: data (decision)` and indented by two spaces per depth.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn to_indented_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut stack = vec![(self.clone(), 0)];
+        while let Some((node, depth)) = stack.pop() {
+            lines.push(format!("{}{}", "  ".repeat(depth), node.label()));
+            // Push in reverse so the first child is visited next
+            stack.extend(
+                node.children()
+                    .into_iter()
+                    .rev()
+                    .map(|child| (child, depth + 1)),
+            );
+        }
+        lines
+    }
+
+    /// Returns one rule per descendant in depth-first order, formatted as
+    /// `id op decision => data` and indented by two spaces per depth, e.g. `a == 1 => x`.
+    ///
+    /// Negated conditions are prefixed with `NOT`, and default children are written as
+    /// `id default`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn export_rules(&self) -> Vec<String> {
+        let mut rules = Vec::new();
+        self.write_rules(&mut rules, 0, false);
+        rules
+    }
+
+    /// Returns the same rules as `export_rules`, but every chain of single-child
+    /// descendants is collapsed into one rule combining their conditions with `AND`,
+    /// e.g. `a == 1 AND b == 2 => x`. Only the data of the last `Node` in a chain is kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn export_rules_compact(&self) -> Vec<String> {
+        let mut rules = Vec::new();
+        self.write_rules(&mut rules, 0, true);
+        rules
+    }
+
+    /// Push the rules of the children of this `Node` and their descendants, collapsing
+    /// single-child chains if `compact` is true.
+    fn write_rules(&self, rules: &mut Vec<String>, depth: usize, compact: bool) {
+        for mut child in self.children() {
+            let mut conditions = vec![child.condition()];
+            while compact && child.len() == 1 {
+                child = child.first().unwrap();
+                conditions.push(child.condition());
+            }
+            let mut rule = format!("{}{}", "  ".repeat(depth), conditions.join(" AND "));
+            if let Some(ref data) = child.0.borrow().data {
+                rule.push_str(&format!(" => {}", data));
+            }
+            rules.push(rule);
+            child.write_rules(rules, depth + 1, compact);
+        }
+    }
+
+    /// Returns the condition of this `Node` as `id op decision`.
+    fn condition(&self) -> String {
+        let self_borrow = self.0.borrow();
+        let symbol = self_borrow.op.as_ref().and_then(Op::symbol);
+        let condition = match (symbol, self_borrow.decision) {
+            (Some(symbol), Some(decision)) => {
+                format!("{} {} {}", self_borrow.id, symbol, decision)
+            }
+            _ => format!("{} default", self_borrow.id),
+        };
+        if self_borrow.negate {
+            format!("NOT {}", condition)
+        } else {
+            condition
+        }
+    }
+
+    /// Returns `id: data (decision)`, leaving out the parts that are `None`.
+    fn label(&self) -> String {
+        let self_borrow = self.0.borrow();
+        let mut label = self_borrow.id.to_string();
+        if let Some(ref data) = self_borrow.data {
+            label.push_str(&format!(": {}", data));
+        }
+        if let Some(ref decision) = self_borrow.decision {
+            label.push_str(&format!(" ({})", decision));
+        }
+        label
+    }
+
+    /// Returns the subtree as YAML, with the same structure as `to_json`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml(&self) -> String {
+        let mut out = String::new();
+        self.write_yaml(&mut out, 0, false);
+        out
+    }
+
+    /// Write the subtree as YAML with its keys indented by `indent` spaces, as an item of
+    /// a sequence if `item` is true.
+    #[cfg(feature = "yaml")]
+    fn write_yaml(&self, out: &mut String, indent: usize, item: bool) {
+        let (fields, children) = self.export_fields();
+        for (index, (name, value)) in fields.iter().enumerate() {
+            // The first key of an item follows its dash
+            if index == 0 && item {
+                out.push_str(&format!("{}- ", " ".repeat(indent - 2)));
+            } else {
+                out.push_str(&" ".repeat(indent));
+            }
+            out.push_str(&format!("{}: {}\n", name, value));
+        }
+        out.push_str(&" ".repeat(indent));
+        if children.is_empty() {
+            out.push_str("children: []\n");
+        } else {
+            out.push_str("children:\n");
+            for child in children {
+                child.write_yaml(out, indent + 4, true);
+            }
+        }
+    }
+
+    /// Returns the exported fields of this `Node` as quoted strings or `null`, together
+    /// with its children.
+    fn export_fields(&self) -> (Fields, Vec<DT<'a, T, U, K>>) {
+        let self_borrow = self.0.borrow();
+        let quote = |value: Option<String>| {
+            value.map_or_else(|| "null".to_string(), |value| json_string(&value))
+        };
+        let fields = [
+            ("id", quote(Some(self_borrow.id.to_string()))),
+            (
+                "op",
+                quote(self_borrow.op.as_ref().map(|op| format!("{:?}", op))),
+            ),
+            (
+                "decision",
+                quote(self_borrow.decision.map(|decision| decision.to_string())),
+            ),
+            (
+                "data",
+                quote(self_borrow.data.as_ref().map(|data| data.to_string())),
+            ),
+        ];
+        (fields, self.children())
+    }
+
+    /// Write the subtree as JSON, compact if `indent` is `None`.
+    fn write_json(&self, out: &mut String, indent: Option<usize>, level: usize) {
+        let newline = |out: &mut String, level: usize| {
+            if let Some(width) = indent {
+                out.push('\n');
+                out.push_str(&" ".repeat(width * level));
+            }
+        };
+        let separator = if indent.is_some() { ": " } else { ":" };
+        let (fields, children) = self.export_fields();
+
+        out.push('{');
+        for (name, value) in fields.iter() {
+            newline(out, level + 1);
+            out.push_str(&format!("\"{}\"{}{},", name, separator, value));
+        }
+        newline(out, level + 1);
+        out.push_str(&format!("\"children\"{}[", separator));
+        for (index, child) in children.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            newline(out, level + 2);
+            child.write_json(out, indent, level + 2);
+        }
+        if !children.is_empty() {
+            newline(out, level + 1);
+        }
+        out.push(']');
+        newline(out, level);
+        out.push('}');
+    }
+}
+
 impl<'a, T, U> DT<'a, T, U>
 where
     U: PartialEq + PartialOrd + Copy,
+{
+    /// Initialize the decision tree.
+    /// It is also possible to use `new`, but there is no reason to give the root any decisions.
+    pub fn init() -> DT<'a, T, U> {
+        DT::init_with_id("root")
+    }
+
+    /// Initialize the decision tree with `data` stored in the root, e.g. for tree-level
+    /// metadata.
+    pub fn init_with_data(data: T) -> DT<'a, T, U> {
+        let dt = DT::init();
+        dt.0.borrow_mut().data = Some(data);
+        dt
+    }
+}
+
+impl<'a, T, U, K> DT<'a, T, U, K>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
 {
     /// Create new instance of a node.
     fn new(
-        id: &'a str,
+        id: K,
         op: Option<Op>,
         data: Option<T>,
         decision: Option<U>,
-        hash: HashLink<'a, T, U>,
-    ) -> DT<'a, T, U> {
+        hash: HashLink<'a, T, U, K>,
+    ) -> DT<'a, T, U, K> {
         DT(Rc::new(RefCell::new(Node {
-            id: id,
-            op: op,
+            id,
+            lifetime: std::marker::PhantomData,
+            op,
             children: Vec::new(),
             latest_parent: None,
             latest_child: None,
-            decision: decision,
-            data: data,
-            hash: hash,
+            decision,
+            data,
+            hash,
+            created_order: CREATED_ORDER.fetch_add(1, Ordering::Relaxed),
+            negate: false,
+            weight: None,
+            priority: 0,
         })))
     }
 
-    /// Initialize the decision tree.
-    /// It is also possible to use `new`, but there is no reason to give the root any decisions.
-    pub fn init() -> DT<'a, T, U> {
+    /// Initialize the decision tree with a root that has the id `id`, e.g. for trees
+    /// keyed by something other than `&str`.
+    pub fn init_with_id(id: K) -> DT<'a, T, U, K> {
         // Initialize the hash map
         let hash = &Rc::new(RefCell::new(std::collections::HashMap::new()));
         // Create new decision tree
-        let dt: DT<'a, T, U> = DT::new("root", None, None, None, hash.clone());
+        let dt: DT<'a, T, U, K> = DT::new(id.clone(), None, None, None, hash.clone());
         // insert the new decision tree into the hash map
-        hash.borrow_mut()
-            .insert("root", Rc::downgrade(&dt.0).clone());
+        hash.borrow_mut().insert(id, Rc::downgrade(&dt.0).clone());
         dt
     }
 
@@ -195,19 +770,67 @@ where
     ///
     /// # Panics
     ///
-    /// Panics if the `Node` has the same id as one that already exist.
-    pub fn append(&mut self, id: &'a str, data: T, decision: U, op: Op) -> DT<'a, T, U> {
-        assert!(
-            !self.0.borrow().hash.borrow().contains_key(id),
-            "Not allowed to append a node with the same id as one that already exist."
-        );
-        let new_child = DT::new(
-            id,
-            Some(op),
-            Some(data),
-            Some(decision),
-            self.0.borrow().hash.clone(),
-        );
+    /// Panics if the `Node` has the same id as one that already exist, or if `op` is
+    /// `Op::Default` and this `Node` already has a default child.
+    pub fn append(&mut self, id: K, data: T, decision: U, op: Op) -> DT<'a, T, U, K> {
+        match self.try_append(id, data, decision, op) {
+            Ok(dt) => dt,
+            Err(AppendError::DuplicateId(_)) => {
+                panic!("Not allowed to append a node with the same id as one that already exist.")
+            }
+            Err(AppendError::DuplicateDefault(_)) => {
+                panic!("Not allowed to append more than one default child to a node.")
+            }
+        }
+    }
+
+    /// Append a new child to this `Node`, or return an `AppendError` if it can't be
+    /// appended. The tree is left unchanged on failure.
+    ///
+    /// Appending with `Op::Default` makes the child the default child, which keeps its
+    /// decision but is only moved to when none of the other children match. Returns
+    /// `AppendError::DuplicateDefault` if this `Node` already has a default child.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently borrowed.
+    pub fn try_append(
+        &mut self,
+        id: K,
+        data: T,
+        decision: U,
+        op: Op,
+    ) -> Result<DT<'a, T, U, K>, AppendError<K>> {
+        self.link_child(id, Some(op), Some(data), Some(decision))
+    }
+
+    /// Append a default child to this `Node`, moved to by `traverse` only when none of
+    /// the other children match, regardless of its position among them.
+    ///
+    /// Returns `AppendError::DuplicateDefault` if this `Node` already has a default child.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently borrowed.
+    pub fn append_default(&mut self, id: K, data: T) -> Result<DT<'a, T, U, K>, AppendError<K>> {
+        self.link_child(id, Some(Op::Default), Some(data), None)
+    }
+
+    /// Create a new child and link it to this `Node` and the hash map.
+    fn link_child(
+        &mut self,
+        id: K,
+        op: Option<Op>,
+        data: Option<T>,
+        decision: Option<U>,
+    ) -> Result<DT<'a, T, U, K>, AppendError<K>> {
+        if self.contains(&id) {
+            return Err(AppendError::DuplicateId(id));
+        }
+        if op == Some(Op::Default) && self.default_child().is_some() {
+            return Err(AppendError::DuplicateDefault(self.id()));
+        }
+        let new_child = DT::new(id.clone(), op, data, decision, self.0.borrow().hash.clone());
         // Insert id
         self.0
             .borrow()
@@ -226,7 +849,72 @@ where
 
         self_borrow.children.push(new_child.0.clone());
 
-        self.clone()
+        Ok(self.clone())
+    }
+
+    /// Move `subtree`, typically a `Node` of another tree, to be the last child of this
+    /// `Node`.
+    ///
+    /// The subtree is detached from its previous parent and its ids are moved into the
+    /// hash map of this tree. Returns `AttachError::WouldCycle` if `subtree` is this
+    /// `Node` or one of its parents, `AttachError::DuplicateId` if any of its ids already
+    /// exist here, and `AttachError::DuplicateDefault` if `subtree` is a default child and
+    /// this `Node` already has one, leaving both trees untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any affected `Node` is currently borrowed.
+    pub fn attach(&mut self, subtree: DT<'a, T, U, K>) -> Result<(), AttachError<K>> {
+        if subtree == *self || self.ancestors().any(|parent| parent == subtree) {
+            return Err(AttachError::WouldCycle(subtree.id()));
+        }
+        if let Some(id) = subtree
+            .iter_dfs()
+            .map(|node| node.id())
+            .find(|id| self.contains(id))
+        {
+            return Err(AttachError::DuplicateId(id));
+        }
+        if subtree.0.borrow().op == Some(Op::Default) && self.default_child().is_some() {
+            return Err(AttachError::DuplicateDefault(self.id()));
+        }
+        if let Some(parent) = subtree.latest_parent() {
+            parent.detach_child(&subtree);
+        }
+        let hash = self.0.borrow().hash.clone();
+        subtree.move_into(&hash);
+        subtree.0.borrow_mut().latest_parent = Some(Rc::downgrade(&self.0));
+        let mut self_borrow = self.0.borrow_mut();
+        self_borrow.latest_child = Some(subtree.0.clone());
+        self_borrow.children.push(subtree.0);
+        Ok(())
+    }
+
+    /// Remove the `Node` with `id`, together with all of its descendants.
+    ///
+    /// The `Node` is detached from its parent and every removed id is moved from the hash
+    /// map into a new one, so a kept handle to the removed `Node` acts as the root of a
+    /// separate tree. Returns `false` if the id doesn't exist or refers to the root.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any affected `Node` is currently borrowed.
+    pub fn remove<Q>(&mut self, id: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + std::hash::Hash + ?Sized,
+    {
+        let node = match self.find(id) {
+            Some(node) => node,
+            None => return false,
+        };
+        match node.latest_parent() {
+            Some(parent) => {
+                parent.detach_child(&node);
+                true
+            }
+            None => false,
+        }
     }
 
     /// If that `Node` exist.
@@ -234,7 +922,11 @@ where
     /// # Panics
     ///
     /// Panics if the `Node` is currently mutably borrowed.
-    pub fn contains(&self, id: &'a str) -> bool {
+    pub fn contains<Q>(&self, id: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + std::hash::Hash + ?Sized,
+    {
         self.0.borrow().hash.borrow().contains_key(id)
     }
 
@@ -252,78 +944,238 @@ where
     /// # Panics
     ///
     /// Panics if the `Node` is currently mutably borrowed.
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         self.0.borrow().children.len()
     }
 
-    /// Returns the decision value inside the node.
+    /// Returns a borrow of the content inside the `Node`, without copying it.
+    ///
+    /// Unlike `content`, it works for any type of content. The `Node` can't be mutably
+    /// borrowed until the returned `Ref` is dropped.
     ///
     /// # Panics
     ///
     /// Panics if the `Node` is currently mutably borrowed.
-    pub fn decision(&self) -> Option<U> {
-        self.0.borrow().decision
+    pub fn content_ref(&self) -> Option<Ref<'_, T>> {
+        Ref::filter_map(self.0.borrow(), |node| node.data.as_ref()).ok()
     }
 
-    /// Returns a reference to the latest parent node.
+    /// Overwrite the content inside the `Node`.
     ///
     /// # Panics
     ///
-    /// Panics if the `Node` is currently mutably borrowed.
-    pub fn latest_parent(&self) -> Option<DT<'a, T, U>> {
-        Some(DT(try_opt!(try_opt!(self
-            .0
-            .borrow()
-            .latest_parent
-            .as_ref())
-        .upgrade())))
+    /// Panics if the `Node` is currently borrowed.
+    pub fn set_content(&self, data: T) {
+        self.0.borrow_mut().data = Some(data);
     }
 
-    /// Returns a reference to the latest child node.
+    /// Mutate the content inside the `Node` in place. Does nothing if there is no content.
     ///
     /// # Panics
     ///
-    /// Panics if the `Node` is currently mutably borrowed.
-    pub fn latest_child(&self) -> Option<DT<'a, T, U>> {
-        Some(DT(try_opt!(self.0.borrow().latest_child.as_ref()).clone()))
+    /// Panics if the `Node` is currently borrowed.
+    pub fn with_content_mut<F>(&self, f: F)
+    where
+        F: FnOnce(&mut T),
+    {
+        if let Some(data) = self.0.borrow_mut().data.as_mut() {
+            f(data);
+        }
     }
 
-    /// Returns a reference to a child by the index
+    /// Returns the unique id of the node.
     ///
     /// # Panics
     ///
     /// Panics if the `Node` is currently mutably borrowed.
-    pub fn child_index(&self, index: usize) -> Option<DT<'a, T, U>> {
-        Some(DT(try_opt!(self.0.borrow().children.get(index)).clone()))
+    pub fn id(&self) -> K {
+        self.0.borrow().id.clone()
     }
 
-    /// Returns a reference to the first child.
+    /// Returns the order in which the node was created.
     ///
+    /// The index increases monotonically with every appended `Node`, which makes it
+    /// usable for stable sorting and tie-breaking. Copies made by `deep_clone`,
+    /// `clone_with_ids` and `graft_clone_of` get fresh indices in the same relative order
+    /// as the originals, while a deserialized tree is numbered in depth-first order.
     ///
     /// # Panics
     ///
     /// Panics if the `Node` is currently mutably borrowed.
-    pub fn first(&self) -> Option<DT<'a, T, U>> {
-        self.child_index(0)
+    pub fn insertion_index(&self) -> usize {
+        self.0.borrow().created_order
     }
 
-    /// Returns a reference to the last child.
-    ///
+    /// Returns the operator of the node, the root has none.
     ///
     /// # Panics
     ///
     /// Panics if the `Node` is currently mutably borrowed.
-    pub fn last(&self) -> Option<DT<'a, T, U>> {
-        self.child_index(self.len() - 1)
+    pub fn op(&self) -> Option<Op> {
+        self.0.borrow().op.clone()
     }
 
-    /// Returns the root of the decision tree.
+    /// Set the operator of every descendant of this `Node` to `op`, leaving this `Node`
+    /// untouched.
+    ///
+    /// Returns `AppendError::DuplicateDefault` with the id of the first `Node` that would
+    /// get more than one default child if `op` is `Op::Default`. The tree is left
+    /// unchanged on failure.
     ///
     /// # Panics
     ///
-    /// Panics if the node is currently mutably borrowed.
-    pub fn root(&self) -> Option<DT<'a, T, U>> {
-        self.find("root")
+    /// Panics if any `Node` in the subtree is currently borrowed.
+    pub fn set_op_recursive(&self, op: Op) -> Result<(), AppendError<K>> {
+        if op == Op::Default {
+            if let Some(node) = self.iter_dfs().find(|node| node.len() > 1) {
+                return Err(AppendError::DuplicateDefault(node.id()));
+            }
+        }
+        for node in self.iter_dfs().skip(1) {
+            node.0.borrow_mut().op = Some(op.clone());
+        }
+        Ok(())
+    }
+
+    /// Negate the operator of the node, e.g. `Op::Greater` becomes "not greater".
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently borrowed.
+    pub fn set_negate(&self, negate: bool) {
+        self.0.borrow_mut().negate = negate;
+    }
+
+    /// Returns true if the operator of the node is negated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn is_negated(&self) -> bool {
+        self.0.borrow().negate
+    }
+
+    /// Set the weight, or cost, of the node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently borrowed.
+    pub fn set_weight(&self, weight: f64) {
+        self.0.borrow_mut().weight = Some(weight);
+    }
+
+    /// Returns the weight of the node if it has been set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn weight(&self) -> Option<f64> {
+        self.0.borrow().weight
+    }
+
+    /// Set the priority of the node. During `traverse`, children with a higher priority
+    /// are evaluated first, and children of equal priority in insertion order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently borrowed.
+    pub fn set_priority(&self, priority: i32) {
+        self.0.borrow_mut().priority = priority;
+    }
+
+    /// Returns the priority of the node, `0` by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn priority(&self) -> i32 {
+        self.0.borrow().priority
+    }
+
+    /// Returns the decision value inside the node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn decision(&self) -> Option<U> {
+        self.0.borrow().decision
+    }
+
+    /// Returns a reference to the latest parent node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn latest_parent(&self) -> Option<DT<'a, T, U, K>> {
+        Some(DT(try_opt!(try_opt!(self
+            .0
+            .borrow()
+            .latest_parent
+            .as_ref())
+        .upgrade())))
+    }
+
+    /// Returns a reference to the latest child node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn latest_child(&self) -> Option<DT<'a, T, U, K>> {
+        Some(DT(try_opt!(self.0.borrow().latest_child.as_ref()).clone()))
+    }
+
+    /// Returns a reference to a child by the index
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn child_index(&self, index: usize) -> Option<DT<'a, T, U, K>> {
+        Some(DT(try_opt!(self.0.borrow().children.get(index)).clone()))
+    }
+
+    /// Call `f` with a reference to each child in order, without collecting them first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed, or if `f` mutably borrows
+    /// this `Node`, since it stays borrowed for the whole iteration.
+    pub fn for_each_child<F>(&self, mut f: F)
+    where
+        F: FnMut(DT<'a, T, U, K>),
+    {
+        for child in self.0.borrow().children.iter() {
+            f(DT(child.clone()));
+        }
+    }
+
+    /// Returns a reference to the first child.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn first(&self) -> Option<DT<'a, T, U, K>> {
+        self.child_index(0)
+    }
+
+    /// Returns a reference to the last child.
+    ///
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently mutably borrowed.
+    pub fn last(&self) -> Option<DT<'a, T, U, K>> {
+        self.child_index(self.len() - 1)
+    }
+
+    /// Returns the root of the decision tree, found by following the parents.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node or any parent is currently mutably borrowed.
+    pub fn root(&self) -> Option<DT<'a, T, U, K>> {
+        std::iter::successors(Some(self.clone()), DT::latest_parent).last()
     }
 
     /// Returns a `Node` based on the steps in the hierarchy.
@@ -332,7 +1184,7 @@ where
     /// # Panics
     ///
     /// Panics if the `None` is currently mutably borrowed.
-    pub fn back(&self, steps: usize) -> Option<DT<'a, T, U>> {
+    pub fn back(&self, steps: usize) -> Option<DT<'a, T, U, K>> {
         if steps > 0 {
             // Recursion
             match self.latest_parent() {
@@ -344,18 +1196,172 @@ where
         }
     }
 
+    /// Returns the `Node` reached by following `child_index` for each index in turn.
+    /// If any index is out of range, return `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a `Node` along the way is currently mutably borrowed.
+    pub fn descendant_at(&self, indices: &[usize]) -> Option<DT<'a, T, U, K>> {
+        indices
+            .iter()
+            .try_fold(self.clone(), |node, &index| node.child_index(index))
+    }
+
     /// Returns the `Node` if it exist.
     ///
     /// # Panics
     ///
     /// Panics if the `Node` is currently mutably borrowed.
-    pub fn find(&self, find_id: &'a str) -> Option<DT<'a, T, U>> {
+    pub fn find<Q>(&self, find_id: &Q) -> Option<DT<'a, T, U, K>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + std::hash::Hash + ?Sized,
+    {
         match self.0.borrow().hash.borrow().get(find_id) {
-            Some(ref x) => Some(DT(try_opt!(x.upgrade()))),
+            Some(x) => Some(DT(try_opt!(x.upgrade()))),
             None => None,
         }
     }
 
+    /// Returns the `Node` with `id` if it exist in the subtree of this `Node`, including
+    /// this `Node` itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` or any parent of the found `Node` is currently mutably
+    /// borrowed.
+    pub fn find_descendant<Q>(&self, id: &Q) -> Option<DT<'a, T, U, K>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + std::hash::Hash + ?Sized,
+    {
+        let node = try_opt!(self.find(id));
+        if std::iter::successors(Some(node.clone()), DT::latest_parent).any(|n| n == *self) {
+            Some(node)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the first `Node` in the subtree, in depth-first order, whose content
+    /// satisfies `pred`. Nodes without content never match.
+    ///
+    /// Each `Node` is only borrowed while `pred` is called on it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn find_by<F>(&self, pred: F) -> Option<DT<'a, T, U, K>>
+    where
+        F: Fn(&T) -> bool,
+    {
+        self.iter_dfs().find(|node| node.data_matches(&pred))
+    }
+
+    /// Returns every `Node` in the subtree, in depth-first order, whose content
+    /// satisfies `pred`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn find_all_by<F>(&self, pred: F) -> Vec<DT<'a, T, U, K>>
+    where
+        F: Fn(&T) -> bool,
+    {
+        self.iter_dfs()
+            .filter(|node| node.data_matches(&pred))
+            .collect()
+    }
+
+    /// Returns the longest sequence of ids that every path in `paths` starts with, e.g.
+    /// to find where recorded traversals diverge. Returns an empty `Vec` if there are no
+    /// paths.
+    pub fn common_prefix(paths: &[Vec<K>]) -> Vec<K> {
+        let (first, rest) = match paths.split_first() {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+        let len = rest.iter().fold(first.len(), |len, path| {
+            first
+                .iter()
+                .zip(path)
+                .take(len)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+        first[..len].to_vec()
+    }
+
+    /// Returns the number of parents between this `Node` and the root, the root has
+    /// depth 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any parent is currently mutably borrowed.
+    pub fn depth(&self) -> usize {
+        self.ancestors().count()
+    }
+
+    /// Returns an iterator over the parents of this `Node`, starting with its latest
+    /// parent and ending with the root.
+    ///
+    /// Each parent is only upgraded and borrowed while advancing.
+    pub fn ancestors(&self) -> Ancestors<'a, T, U, K> {
+        Ancestors {
+            next: self.0.borrow().latest_parent.clone(),
+        }
+    }
+
+    /// Returns this `Node` followed by all of its parents, ending with the root.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any parent is currently mutably borrowed.
+    pub fn path_to_root(&self) -> Vec<DT<'a, T, U, K>> {
+        std::iter::once(self.clone())
+            .chain(self.ancestors())
+            .collect()
+    }
+
+    /// Returns the length of the longest path from this `Node` down to a leaf, a leaf
+    /// has height 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn height(&self) -> usize {
+        self.children()
+            .into_iter()
+            .map(|child| child.height() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of nodes at each depth below this `Node`, indexed by depth
+    /// relative to it, so the first count is always 1 for the `Node` itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn depth_profile(&self) -> Vec<usize> {
+        let mut profile = Vec::new();
+        let mut queue = std::collections::VecDeque::from([(self.0.clone(), 0)]);
+        while let Some((link, depth)) = queue.pop_front() {
+            if depth == profile.len() {
+                profile.push(0);
+            }
+            profile[depth] += 1;
+            queue.extend(
+                link.borrow()
+                    .children
+                    .iter()
+                    .map(|child| (child.clone(), depth + 1)),
+            );
+        }
+        profile
+    }
+
     /// Returns true if it has any children.
     ///
     /// # Panics
@@ -382,76 +1388,977 @@ where
     pub fn is_root(&self) -> bool {
         self.latest_parent().is_none()
     }
+
+    /// Returns one row per leaf below this `Node`, where each column is the decision
+    /// at that depth along the path to the leaf, starting with this `Node`.
+    ///
+    /// Rows of shallower leaves are padded with `None` so all rows align by depth.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn to_decision_table(&self) -> Vec<Vec<Option<U>>> {
+        let mut rows = Vec::new();
+        self.collect_decision_rows(&mut Vec::new(), &mut rows);
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        for row in rows.iter_mut() {
+            row.resize(width, None);
+        }
+        rows
+    }
+
+    /// Push the decisions along every path from this `Node` to a leaf.
+    fn collect_decision_rows(&self, path: &mut Vec<Option<U>>, rows: &mut Vec<Vec<Option<U>>>) {
+        path.push(self.decision());
+        let children = self.children();
+        if children.is_empty() {
+            rows.push(path.clone());
+        }
+        for child in children {
+            child.collect_decision_rows(path, rows);
+        }
+        path.pop();
+    }
+
+    /// Returns an iterator visiting this `Node` and its descendants depth-first, yielding
+    /// every `Node` before its children in left-to-right order.
+    ///
+    /// Nodes are only borrowed while advancing, so the tree can be modified in between.
+    pub fn iter_dfs(&self) -> DepthFirst<'a, T, U, K> {
+        DepthFirst {
+            stack: vec![self.0.clone()],
+        }
+    }
+
+    /// Returns an iterator visiting this `Node` and its descendants breadth-first, level
+    /// by level.
+    ///
+    /// Nodes are only borrowed while advancing, so the tree can be modified in between.
+    pub fn iter_bfs(&self) -> BreadthFirst<'a, T, U, K> {
+        BreadthFirst {
+            queue: std::iter::once(self.0.clone()).collect(),
+        }
+    }
+
+    /// Returns the ids and nesting of the subtree, ignoring data and decisions,
+    /// e.g. `root(a(c),b)`.
+    ///
+    /// Two subtrees with the same shape and ids produce identical skeletons.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn id_skeleton(&self) -> String
+    where
+        K: std::fmt::Display,
+    {
+        let children = self.children();
+        let mut skeleton = self.id().to_string();
+        if !children.is_empty() {
+            let inner: Vec<String> = children
+                .into_iter()
+                .map(|child| child.id_skeleton())
+                .collect();
+            skeleton.push('(');
+            skeleton.push_str(&inner.join(","));
+            skeleton.push(')');
+        }
+        skeleton
+    }
+
+    /// Keep only the direct children whose entire subtree satisfies `pred`.
+    ///
+    /// A child is removed, together with all of its descendants, as soon as any
+    /// `Node` in its subtree fails the predicate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently borrowed.
+    pub fn retain_subtree_if<F>(&self, pred: F)
+    where
+        F: Fn(&DT<'a, T, U, K>) -> bool,
+    {
+        for child in self.children() {
+            if !child.subtree_all(&pred) {
+                self.detach_child(&child);
+            }
+        }
+    }
+
+    /// Merge leaves below this `Node` that share the same `key`.
+    ///
+    /// The first leaf of every group, in depth-first order, is kept and `combine` is
+    /// called with its data and the data of each redundant leaf, which is then removed.
+    /// Leaves without data are left untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently borrowed.
+    pub fn merge_leaves_by<Key, F, G>(&self, key: F, combine: G)
+    where
+        Key: Eq + std::hash::Hash,
+        F: Fn(&T) -> Key,
+        G: Fn(&mut T, &T),
+    {
+        let mut groups: std::collections::HashMap<Key, DT<'a, T, U, K>> =
+            std::collections::HashMap::new();
+        for leaf in self.leaves() {
+            if leaf == *self {
+                continue;
+            }
+            let leaf_key = match leaf.0.borrow().data.as_ref() {
+                Some(data) => key(data),
+                None => continue,
+            };
+            match groups.get(&leaf_key) {
+                Some(kept) => {
+                    if let (Some(kept_data), Some(data)) = (
+                        kept.0.borrow_mut().data.as_mut(),
+                        leaf.0.borrow().data.as_ref(),
+                    ) {
+                        combine(kept_data, data);
+                    }
+                    if let Some(parent) = leaf.latest_parent() {
+                        parent.detach_child(&leaf);
+                    }
+                }
+                None => {
+                    groups.insert(leaf_key, leaf);
+                }
+            }
+        }
+    }
+
+    /// Traverse from this `Node` with every input sequence of decisions and report which
+    /// leaves are reached and which are never reached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn coverage(&self, inputs: &[Vec<U>], op: PartialOp) -> CoverageReport<K> {
+        let reached: Vec<DT<'a, T, U, K>> = inputs
+            .iter()
+            .map(|input| Traverse::start(self.clone()).replay_decisions(input, op).1)
+            .collect();
+        let mut report = CoverageReport {
+            reached: Vec::new(),
+            unreached: Vec::new(),
+        };
+        for leaf in self.leaves() {
+            if reached.contains(&leaf) {
+                report.reached.push(leaf.id());
+            } else {
+                report.unreached.push(leaf.id());
+            }
+        }
+        report
+    }
+
+    /// Traverse from this `Node` with every input sequence of decisions and remove every
+    /// descendant that no input visits. The removed ids are dropped from the hash map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently borrowed.
+    pub fn trim_to_reachable(&self, inputs: &[Vec<U>], op: PartialOp) {
+        let mut visited = std::collections::HashSet::new();
+        for input in inputs {
+            let mut travel = Traverse::start(self.clone());
+            travel.replay_decisions(input, op);
+            visited.extend(travel.visited().map(Rc::as_ptr));
+        }
+        let mut stack = vec![self.clone()];
+        while let Some(node) = stack.pop() {
+            for child in node.children() {
+                if visited.contains(&Rc::as_ptr(&child.0)) {
+                    stack.push(child);
+                } else {
+                    node.detach_child(&child);
+                }
+            }
+        }
+    }
+
+    /// Returns every leaf below this `Node` in depth-first order.
+    fn leaves(&self) -> Vec<DT<'a, T, U, K>> {
+        let children = self.children();
+        if children.is_empty() {
+            return vec![self.clone()];
+        }
+        children
+            .into_iter()
+            .flat_map(|child| child.leaves())
+            .collect()
+    }
+
+    /// Returns true if walking the subtree reaches the same `Node` twice.
+    ///
+    /// It is a cheap guard to run before recursive operations, which would otherwise
+    /// never terminate on a cyclic tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn contains_cycle(&self) -> bool {
+        let mut visited = std::collections::HashSet::new();
+        let mut stack = vec![self.0.clone()];
+        while let Some(link) = stack.pop() {
+            if !visited.insert(Rc::as_ptr(&link)) {
+                return true;
+            }
+            stack.extend(link.borrow().children.iter().cloned());
+        }
+        false
+    }
+
+    /// Check that every `Node` in the tree, except the root, has a live parent that lists
+    /// it as a child.
+    ///
+    /// The root is the first `Node`, in insertion order, that has never been given a
+    /// parent and that no other `Node` lists as a child. A `Node` whose parent has been
+    /// dropped is therefore reported, even when it is the only one left in the tree.
+    /// Returns the ids of the offending nodes in insertion order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the tree is currently mutably borrowed.
+    pub fn validate_no_orphans(&self) -> Result<(), Vec<K>> {
+        let mut nodes: Vec<DT<'a, T, U, K>> = self
+            .0
+            .borrow()
+            .hash
+            .borrow()
+            .values()
+            .filter_map(Weak::upgrade)
+            .map(DT)
+            .collect();
+        nodes.sort_by_key(DT::insertion_index);
+        let listed: std::collections::HashSet<_> = nodes
+            .iter()
+            .flat_map(|node| {
+                node.0
+                    .borrow()
+                    .children
+                    .iter()
+                    .map(Rc::as_ptr)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let root = nodes
+            .iter()
+            .find(|node| {
+                node.0.borrow().latest_parent.is_none() && !listed.contains(&Rc::as_ptr(&node.0))
+            })
+            .cloned();
+        let orphans: Vec<K> = nodes
+            .into_iter()
+            .filter(|node| Some(node) != root.as_ref())
+            .filter(|node| {
+                !node.latest_parent().is_some_and(|parent| {
+                    parent
+                        .0
+                        .borrow()
+                        .children
+                        .iter()
+                        .any(|child| Rc::ptr_eq(child, &node.0))
+                })
+            })
+            .map(|node| node.id())
+            .collect();
+        if orphans.is_empty() {
+            Ok(())
+        } else {
+            Err(orphans)
+        }
+    }
+
+    /// Sort the children of this `Node` with the comparator function `compare`.
+    ///
+    /// The sort is stable, and changes which child `traverse` reaches first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently borrowed.
+    pub fn sort_children_by<F>(&self, mut compare: F)
+    where
+        F: FnMut(&DT<'a, T, U, K>, &DT<'a, T, U, K>) -> std::cmp::Ordering,
+    {
+        let mut children = self.children();
+        children.sort_by(|a, b| compare(a, b));
+        self.0.borrow_mut().children = children.into_iter().map(|child| child.0).collect();
+    }
+
+    /// Sort the children of this `Node` by their decision, ascending or descending.
+    ///
+    /// Decisions that cannot be compared keep their relative order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` is currently borrowed.
+    pub fn sort_children_by_decision(&self, ascending: bool) {
+        self.sort_children_by(|a, b| {
+            let ordering = a
+                .decision()
+                .partial_cmp(&b.decision())
+                .unwrap_or(std::cmp::Ordering::Equal);
+            if ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+    }
+
+    /// Returns the number of descendants whose operator equals `op`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently mutably borrowed.
+    pub fn count_nodes_where_op(&self, op: &Op) -> usize {
+        self.children()
+            .into_iter()
+            .map(|child| {
+                let matched = child.0.borrow().op.as_ref() == Some(op);
+                matched as usize + child.count_nodes_where_op(op)
+            })
+            .sum()
+    }
+
+    /// Returns every direct child in order whose decision satisfies `op` against
+    /// `decision`, using the same comparison as `Traverse::traverse`. For aggregate
+    /// operators, returns the children sharing the selected decision. Default children
+    /// are not included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Node` or any of its children is currently mutably borrowed.
+    pub fn children_matching(&self, decision: &U, op: PartialOp) -> Vec<DT<'a, T, U, K>> {
+        let target = if op.is_aggregate() {
+            match self.aggregate_child(op) {
+                Some(child) => child.decision(),
+                None => return Vec::new(),
+            }
+        } else {
+            None
+        };
+        self.children()
+            .into_iter()
+            .filter(|child| {
+                let child_borrow = child.0.borrow();
+                if child_borrow.op == Some(Op::Default) {
+                    return false;
+                }
+                match child_borrow.decision.as_ref() {
+                    Some(child_decision) if op.is_aggregate() => Some(*child_decision) == target,
+                    Some(child_decision) => {
+                        op.matches(decision, child_decision) != child_borrow.negate
+                    }
+                    None => false,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the first child, in order of priority, whose operator and decision satisfy
+    /// `matches`. Negated children invert the result, and children without a decision
+    /// never match.
+    fn first_match<F>(&self, mut matches: F) -> Option<DT<'a, T, U, K>>
+    where
+        F: FnMut(&Op, &U) -> bool,
+    {
+        let mut children = self.children();
+        // Evaluate by priority, stable so insertion order breaks ties
+        children.sort_by_key(|child| std::cmp::Reverse(child.0.borrow().priority));
+        children.into_iter().find(|child| {
+            let child_borrow = child.0.borrow();
+            match (child_borrow.op.as_ref(), child_borrow.decision.as_ref()) {
+                // Only moved to when nothing else matches
+                (Some(Op::Default), _) => false,
+                (Some(op), Some(decision)) => matches(op, decision) != child_borrow.negate,
+                _ => false,
+            }
+        })
+    }
+
+    /// Returns the children in order, without holding a borrow of this `Node`.
+    fn children(&self) -> Vec<DT<'a, T, U, K>> {
+        self.0.borrow().children.iter().cloned().map(DT).collect()
+    }
+
+    /// Returns the default child, if any.
+    fn default_child(&self) -> Option<DT<'a, T, U, K>> {
+        self.0
+            .borrow()
+            .children
+            .iter()
+            .find(|child| child.borrow().op == Some(Op::Default))
+            .map(|child| DT(child.clone()))
+    }
+
+    /// Returns the child selected by the aggregate `op` among the children with a
+    /// decision. Equal decisions keep their insertion order.
+    fn aggregate_child(&self, op: PartialOp) -> Option<DT<'a, T, U, K>> {
+        let mut children: Vec<Link<'a, T, U, K>> = self
+            .0
+            .borrow()
+            .children
+            .iter()
+            .filter(|child| {
+                let child_borrow = child.borrow();
+                child_borrow.decision.is_some() && child_borrow.op != Some(Op::Default)
+            })
+            .cloned()
+            .collect();
+        children.sort_by(|a, b| {
+            a.borrow()
+                .decision
+                .partial_cmp(&b.borrow().decision)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let last = try_opt!(children.len().checked_sub(1));
+        let index = match op {
+            PartialOp::Min => 0,
+            PartialOp::Max => last,
+            PartialOp::Median => last / 2,
+            _ => return None,
+        };
+        Some(DT(children[index].clone()))
+    }
+
+    /// Remove every `Node` more than `depth` levels below this `Node`, so that nodes at
+    /// exactly `depth` become leaves. The removed ids are dropped from the hash map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any `Node` in the subtree is currently borrowed.
+    pub fn truncate_at_depth(&self, depth: usize) {
+        for child in self.children() {
+            match depth {
+                0 => self.detach_child(&child),
+                _ => child.truncate_at_depth(depth - 1),
+            }
+        }
+    }
+
+    /// Returns true if this `Node` has content that satisfies `pred`.
+    fn data_matches<F>(&self, pred: &F) -> bool
+    where
+        F: Fn(&T) -> bool,
+    {
+        self.0.borrow().data.as_ref().is_some_and(pred)
+    }
+
+    /// Returns true if this `Node` and all of its descendants satisfy `pred`.
+    fn subtree_all<F>(&self, pred: &F) -> bool
+    where
+        F: Fn(&DT<'a, T, U, K>) -> bool,
+    {
+        if !pred(self) {
+            return false;
+        }
+        self.children()
+            .into_iter()
+            .all(|child| child.subtree_all(pred))
+    }
+
+    /// Detach `child` from this `Node` and remove its subtree from the hash map.
+    fn detach_child(&self, child: &DT<'a, T, U, K>) {
+        {
+            let mut self_borrow = self.0.borrow_mut();
+            self_borrow
+                .children
+                .retain(|link| !Rc::ptr_eq(link, &child.0));
+            if let Some(ref latest) = self_borrow.latest_child {
+                if Rc::ptr_eq(latest, &child.0) {
+                    self_borrow.latest_child = self_borrow.children.last().cloned();
+                }
+            }
+        }
+        child.0.borrow_mut().latest_parent = None;
+        child.move_into(&Rc::new(RefCell::new(std::collections::HashMap::new())));
+    }
+
+    /// Move the id of this `Node` and of all its descendants from their hash map into
+    /// `hash`, which every moved `Node` then refers to.
+    fn move_into(&self, hash: &HashLink<'a, T, U, K>) {
+        for node in self.iter_dfs() {
+            let mut node_borrow = node.0.borrow_mut();
+            node_borrow.hash.borrow_mut().remove(&node_borrow.id);
+            hash.borrow_mut()
+                .insert(node_borrow.id.clone(), Rc::downgrade(&node.0));
+            node_borrow.hash = hash.clone();
+        }
+    }
+}
+
+/// Depth-first iterator over a subtree, created by `DT::iter_dfs`.
+pub struct DepthFirst<'a, T, U, K = &'a str>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    stack: Vec<Link<'a, T, U, K>>,
+}
+
+impl<'a, T, U, K> Iterator for DepthFirst<'a, T, U, K>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    type Item = DT<'a, T, U, K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let link = try_opt!(self.stack.pop());
+        // Push in reverse so the first child is visited next
+        self.stack
+            .extend(link.borrow().children.iter().rev().cloned());
+        Some(DT(link))
+    }
+}
+
+/// Iterator over the parents of a `Node` up to the root, created by `DT::ancestors`.
+pub struct Ancestors<'a, T, U, K = &'a str>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    next: Option<WeakLink<'a, T, U, K>>,
+}
+
+impl<'a, T, U, K> Iterator for Ancestors<'a, T, U, K>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    type Item = DT<'a, T, U, K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let link = try_opt!(try_opt!(self.next.take()).upgrade());
+        self.next = link.borrow().latest_parent.clone();
+        Some(DT(link))
+    }
 }
 
-pub struct Traverse<'a, T, U>
+/// Breadth-first iterator over a subtree, created by `DT::iter_bfs`.
+pub struct BreadthFirst<'a, T, U, K = &'a str>
 where
     U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
 {
-    current: Link<'a, T, U>,
+    queue: std::collections::VecDeque<Link<'a, T, U, K>>,
 }
 
-impl<'a, T, U> Traverse<'a, T, U>
+impl<'a, T, U, K> Iterator for BreadthFirst<'a, T, U, K>
 where
     U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    type Item = DT<'a, T, U, K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let link = try_opt!(self.queue.pop_front());
+        self.queue.extend(link.borrow().children.iter().cloned());
+        Some(DT(link))
+    }
+}
+
+pub struct Traverse<'a, T, U, K = &'a str>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    current: Link<'a, T, U, K>,
+    start: Link<'a, T, U, K>,
+    history: Vec<Step<'a, T, U, K>>,
+}
+
+/// A move made by `Traverse`, together with the operator that matched it.
+struct Step<'a, T, U, K>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    node: Link<'a, T, U, K>,
+    op: &'static str,
+}
+
+impl<'a, T, U, K> Clone for Step<'a, T, U, K>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    fn clone(&self) -> Self {
+        Step {
+            node: self.node.clone(),
+            op: self.op,
+        }
+    }
+}
+
+impl<'a, T, U, K> Traverse<'a, T, U, K>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
 {
     /// Start node to traverse from.
-    pub fn start(node: DT<'a, T, U>) -> Traverse<'a, T, U> {
-        Traverse { current: node.0 }
+    pub fn start(node: DT<'a, T, U, K>) -> Traverse<'a, T, U, K> {
+        Traverse {
+            current: node.0.clone(),
+            start: node.0,
+            history: Vec::new(),
+        }
     }
 
     /// Return current as a decision tree node.
-    pub fn dt(&self) -> DT<'a, T, U> {
+    pub fn dt(&self) -> DT<'a, T, U, K> {
         DT(self.current.clone())
     }
-    // Some(DT(try_opt!(self.0.borrow().latest_child.as_ref()).clone()))
+
+    /// Returns the id of the current node.
+    pub fn current_id(&self) -> K {
+        self.current.borrow().id.clone()
+    }
+
+    /// Assert that the current node has `id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics with both ids if the current node has a different id.
+    pub fn assert_reached<Q>(&self, id: &Q)
+    where
+        K: std::borrow::Borrow<Q> + std::fmt::Debug,
+        Q: Eq + std::fmt::Debug + ?Sized,
+    {
+        let current_id = self.current_id();
+        assert!(
+            current_id.borrow() == id,
+            "Expected to reach {:?}, but the current node is {:?}.",
+            id,
+            current_id
+        );
+    }
+
+    /// Returns true if the node with `id` is the current node or one of its descendants.
+    pub fn can_reach<Q>(&self, id: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + std::hash::Hash + ?Sized,
+    {
+        self.dt().find_descendant(id).is_some()
+    }
+
+    /// Returns an independent traverser at the same position and with the same history.
+    ///
+    /// Traversing the fork does not affect this traverser, the nodes are still shared.
+    pub fn fork(&self) -> Traverse<'a, T, U, K> {
+        Traverse {
+            current: self.current.clone(),
+            start: self.start.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    /// Returns every visited node in order, including the start node.
+    pub fn path_nodes(&self) -> Vec<DT<'a, T, U, K>> {
+        self.visited().map(|link| DT(link.clone())).collect()
+    }
+
+    /// Returns the ids of every visited node in order, including the start node.
+    pub fn history_ids(&self) -> Vec<K> {
+        self.visited()
+            .map(|link| link.borrow().id.clone())
+            .collect()
+    }
+
+    /// Returns the sum of the weights of every visited node, including the start node.
+    ///
+    /// Nodes without a weight count as `0.0`.
+    pub fn path_weight(&self) -> f64 {
+        self.visited().filter_map(|link| link.borrow().weight).sum()
+    }
+
+    /// Returns how many times the node with `id` appears in the history, including the
+    /// start node.
+    pub fn visited_count<Q>(&self, id: &Q) -> usize
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + std::hash::Hash + ?Sized,
+    {
+        self.visited()
+            .filter(|link| link.borrow().id.borrow() == id)
+            .count()
+    }
+
+    /// Returns how many times each decision appears among the visited nodes, including
+    /// the start node. Nodes without a decision are not counted.
+    pub fn visited_decision_histogram(&self) -> std::collections::HashMap<U, usize>
+    where
+        U: Eq + std::hash::Hash,
+    {
+        let mut histogram = std::collections::HashMap::new();
+        for decision in self.visited().filter_map(|link| link.borrow().decision) {
+            *histogram.entry(decision).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Returns the number of steps taken since the start node.
+    pub fn stack_depth(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Returns the visited path, including the start node, and resets `current` back to
+    /// the start with a cleared history.
+    pub fn take_path(&mut self) -> Vec<DT<'a, T, U, K>> {
+        let path = self.path_nodes();
+        self.reset();
+        path
+    }
+
+    /// Undo the last step, moving `current` back to the previous node.
+    ///
+    /// Returns the previous node, or `None` if already at the start node.
+    pub fn back(&mut self) -> Option<DT<'a, T, U, K>> {
+        try_opt!(self.history.pop());
+        self.current = match self.history.last() {
+            Some(step) => step.node.clone(),
+            None => self.start.clone(),
+        };
+        Some(self.dt())
+    }
+
+    /// Undo the last step and traverse from the previous node with `decision` and `op`
+    /// instead.
+    ///
+    /// Returns the node moved to. If there is no step to undo, or no child matches from
+    /// the previous node, the last step is kept and `None` is returned.
+    pub fn retry(&mut self, decision: &U, op: PartialOp) -> Option<DT<'a, T, U, K>> {
+        let step = try_opt!(self.history.last().cloned());
+        self.back();
+        let moved = self.traverse(decision, op);
+        if moved.is_none() {
+            self.current = step.node.clone();
+            self.history.push(step);
+        }
+        moved
+    }
+
+    /// Move `current` back to the start node and clear the history.
+    pub fn reset(&mut self) {
+        self.current = self.start.clone();
+        self.history.clear();
+    }
+
+    /// Move `current` and the start node to the root and clear the history, then move to
+    /// the child with each of `ids` in turn.
+    ///
+    /// Returns the last node, or `None` if an id is not a child of the current node, in
+    /// which case `current` stays at the last node followed.
+    pub fn reset_and_follow(&mut self, ids: &[K]) -> Option<DT<'a, T, U, K>> {
+        let root = try_opt!(self.dt().root());
+        self.start = root.0.clone();
+        self.reset();
+        for id in ids {
+            let child = try_opt!(self
+                .current
+                .borrow()
+                .children
+                .iter()
+                .find(|child| child.borrow().id == *id)
+                .cloned());
+            let op = child.borrow().op.as_ref().map_or("", Op::name);
+            self.visit(child, op);
+        }
+        Some(self.dt())
+    }
+
+    /// Returns how many times each operator was the one matching a step in the history.
+    ///
+    /// Operators are keyed by name, e.g. `"Equal"`.
+    pub fn op_usage(&self) -> std::collections::HashMap<String, usize> {
+        let mut usage = std::collections::HashMap::new();
+        for step in self.history.iter() {
+            *usage.entry(step.op.to_string()).or_insert(0) += 1;
+        }
+        usage
+    }
+
+    /// Returns every visited node in order, including the start node.
+    fn visited(&self) -> impl Iterator<Item = &Link<'a, T, U, K>> {
+        std::iter::once(&self.start).chain(self.history.iter().map(|step| &step.node))
+    }
+
+    /// Move to the default child of `current`, if any.
+    fn visit_default(&mut self) -> Option<DT<'a, T, U, K>> {
+        let child = try_opt!(self.dt().default_child());
+        Some(self.visit(child.0, Op::Default.name()))
+    }
+
+    /// Move to `link` and record it in the history, together with the matching `op`.
+    fn visit(&mut self, link: Link<'a, T, U, K>, op: &'static str) -> DT<'a, T, U, K> {
+        self.current = link.clone();
+        self.history.push(Step {
+            node: link.clone(),
+            op,
+        });
+        DT(link)
+    }
     /// Traverse to next node based on its decision.
     ///
+    /// Moves to the first child, in order of priority, whose decision satisfies `op`
+    /// against `decision`. Aggregate operators instead move to the child they select.
+    /// If none of the children is met, return `None`.
+    pub fn traverse(&mut self, decision: &U, op: PartialOp) -> Option<DT<'a, T, U, K>> {
+        if !op.is_aggregate() {
+            return self.traverse_with(decision, &op);
+        }
+        match self.dt().aggregate_child(op) {
+            Some(child) => Some(self.visit(child.0, op.name())),
+            None => self.visit_default(),
+        }
+    }
+
+    /// Traverse to next node using `strategy` to match `decision` against the decision
+    /// of each child.
+    ///
+    /// Moves to the first child, in order of priority, that the strategy matches, or else
+    /// to the default child. If none of the children is met, return `None`.
+    pub fn traverse_with<M>(&mut self, decision: &U, strategy: &M) -> Option<DT<'a, T, U, K>>
+    where
+        M: MatchStrategy<U>,
+    {
+        let child = self
+            .dt()
+            .first_match(|_, child_decision| strategy.matches(decision, child_decision));
+        match child {
+            Some(child) => Some(self.visit(child.0, strategy.name())),
+            None => self.visit_default(),
+        }
+    }
+
+    /// Returns every child of the current node that satisfies `op` against `decision`,
+    /// in child order, without moving.
+    pub fn matches(&self, decision: &U, op: PartialOp) -> Vec<DT<'a, T, U, K>> {
+        self.dt().children_matching(decision, op)
+    }
+
+    /// Traverse to next node using the operator stored in each child at `append`.
+    ///
     /// If none of the operations is met, return `None`.
-    pub fn traverse(&mut self, decision: &U) -> Option<DT<'a, T, U>> {
-        // If the node has any children
-        if self.current.borrow().children.len() > 0 {
-            for child in self.current.clone().borrow().children.iter() {
-                let child_borrow = &child.borrow();
-                // Continue if decision is none
-                if child_borrow.decision.is_none() {
-                    continue;
-                }
-                match child_borrow.clone().op.as_ref().unwrap() {
-                    Op::Greater => {
-                        if decision > &child_borrow.decision.unwrap() {
-                            self.current = child.clone();
-                            return Some(DT(child.clone()));
-                        }
-                    }
-                    Op::GreaterEqual => {
-                        if decision >= &child_borrow.decision.unwrap() {
-                            self.current = child.clone();
-                            return Some(DT(child.clone()));
-                        }
-                    }
-                    Op::Less => {
-                        if decision < &child_borrow.decision.unwrap() {
-                            self.current = child.clone();
-                            return Some(DT(child.clone()));
-                        }
-                    }
-                    Op::LessEqual => {
-                        if decision <= &child_borrow.decision.unwrap() {
-                            self.current = child.clone();
-                            return Some(DT(child.clone()));
-                        }
-                    }
-                    Op::Equal => {
-                        if decision == &child_borrow.decision.unwrap() {
-                            self.current = child.clone();
-                            return Some(DT(child.clone()));
-                        }
-                    }
-                    _ => panic!("{:?} is not supported", child_borrow.op.as_ref().unwrap()),
-                }
-            }
+    pub fn advance(&mut self, decision: &U) -> Option<DT<'a, T, U, K>> {
+        let child = self
+            .dt()
+            .first_match(|op, child_decision| op.matches(decision, child_decision));
+        let child = match child {
+            Some(child) => child,
+            None => return self.visit_default(),
+        };
+        let op = child.0.borrow().op.as_ref().map_or("", Op::name);
+        Some(self.visit(child.0, op))
+    }
+
+    /// Traverse with each decision in turn, stopping at the first one that matches no
+    /// child.
+    ///
+    /// Returns whether every decision matched, together with the current node.
+    pub fn replay_decisions(&mut self, decisions: &[U], op: PartialOp) -> (bool, DT<'a, T, U, K>) {
+        let all_matched = decisions
+            .iter()
+            .all(|decision| self.traverse(decision, op).is_some());
+        (all_matched, self.dt())
+    }
+
+    /// Keep traversing with the same `decision` and `op` until no child matches.
+    ///
+    /// Returns every node moved to in order, and leaves `current` at the last one. If the
+    /// first step matches nothing, the returned `Vec` is empty.
+    pub fn run(&mut self, decision: &U, op: PartialOp) -> Vec<DT<'a, T, U, K>> {
+        std::iter::from_fn(|| self.traverse(decision, op)).collect()
+    }
+}
+
+/// Returns `value` as a quoted and escaped JSON string, which is also a valid
+/// double-quoted YAML scalar.
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
         }
-        None
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_cycle() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal);
+        assert!(!tree.contains_cycle());
+
+        // Link the root back in as a child of its own child
+        let child = tree.find("1").unwrap();
+        child.0.borrow_mut().children.push(tree.0.clone());
+        assert!(tree.contains_cycle());
+        assert!(child.contains_cycle());
+
+        // Break the cycle so the nodes can be dropped
+        child.0.borrow_mut().children.clear();
+    }
+
+    #[test]
+    fn test_validate_no_orphans() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .find("2")
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal);
+        assert!(tree.validate_no_orphans() == Ok(()));
+
+        // Drop the parent link of a node while its parent still lists it
+        let first = tree.find("1").unwrap();
+        first.0.borrow_mut().latest_parent = None;
+        assert!(tree.validate_no_orphans() == Err(vec!["1"]));
+
+        // Unlist a node from its parent while it still points at the parent
+        let second = tree.find("2").unwrap();
+        tree.0
+            .borrow_mut()
+            .children
+            .retain(|child| !Rc::ptr_eq(child, &second.0));
+        assert!(tree.validate_no_orphans() == Err(vec!["1", "2"]));
+        assert!(tree.find("3").unwrap().validate_no_orphans() == Err(vec!["1", "2"]));
+    }
+
+    #[test]
+    fn test_visited_count() {
+        let mut tree = DT::init();
+        tree.append("shared", "data", 1, Op::Equal);
+
+        // Share the node with itself so a path can pass through it twice
+        let shared = tree.find("shared").unwrap();
+        shared.0.borrow_mut().children.push(shared.0.clone());
+
+        let mut travel = Traverse::start(tree);
+        travel.traverse(&1, PartialOp::Equal);
+        travel.traverse(&1, PartialOp::Equal);
+        assert!(travel.visited_count("shared") == 2);
+        assert!(travel.visited_count("root") == 1);
+        assert!(travel.visited_count("missing") == 0);
+
+        // Break the cycle so the nodes can be dropped
+        shared.0.borrow_mut().children.clear();
     }
 }
@@ -0,0 +1,54 @@
+//! Macros for building decision trees.
+
+/// Build a decision tree from a nested list of nodes.
+///
+/// Every node is written as `id => (data, decision, operator)`, optionally followed by
+/// a block with its children. The macro expands into `DT::init()` followed by the
+/// `append` and `find` calls wiring each node under its parent, so ids have to be
+/// unique just like when using `append` directly. Each id is evaluated once.
+///
+/// # Examples
+///
+/// ```
+/// use cdt::{decision_tree, Op};
+///
+/// let tree = decision_tree! {
+///     "first" => ("banana", true, Op::Equal),
+///     "second" => ("apple", false, Op::Equal) {
+///         "fourth" => ("red apple", true, Op::Equal),
+///         "fifth" => ("green apple", false, Op::Equal),
+///     },
+/// };
+///
+/// assert!(tree.tree_len() == 5);
+/// assert!(tree.find("fourth").unwrap().latest_parent().unwrap().id() == "second");
+/// ```
+///
+/// # Panics
+///
+/// Panics if the same id is used more than once.
+#[macro_export]
+macro_rules! decision_tree {
+    (@children $parent:ident;) => {};
+    (@children $parent:ident;
+        $id:expr => ($data:expr, $decision:expr, $op:expr $(,)?)
+        $({ $($children:tt)* })?
+        $(, $($rest:tt)*)?
+    ) => {
+        {
+            let id = $id;
+            $parent.append(id, $data, $decision, $op);
+            $({
+                let mut child = $parent.find(id).unwrap();
+                $crate::decision_tree!(@children child; $($children)*);
+            })?
+        }
+        $($crate::decision_tree!(@children $parent; $($rest)*);)?
+    };
+    ($($children:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut tree = $crate::DT::init();
+        $crate::decision_tree!(@children tree; $($children)*);
+        tree
+    }};
+}
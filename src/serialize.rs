@@ -0,0 +1,113 @@
+//! Serialization of decision trees, enabled by the `serde` feature.
+//!
+//! A `DT` is serialized as its subtree, where every `Node` is a struct with its id,
+//! operator, decision, data and nested children. Deserializing rebuilds the parent
+//! links and the hash map, with the deserialized `Node` as the root. Insertion indices
+//! are not serialized, so the deserialized nodes are numbered in depth-first order.
+
+use super::{AppendError, HashLink, Op, DT};
+use serde::de::Error;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+impl<'a, T, U, K> Serialize for DT<'a, T, U, K>
+where
+    T: Serialize,
+    U: PartialEq + PartialOrd + Copy + Serialize,
+    K: Eq + std::hash::Hash + Clone + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let self_borrow = self.0.borrow();
+        let children = self.children();
+
+        let mut state = serializer.serialize_struct("Node", 8)?;
+        state.serialize_field("id", &self_borrow.id)?;
+        state.serialize_field("op", &self_borrow.op)?;
+        state.serialize_field("decision", &self_borrow.decision)?;
+        state.serialize_field("data", &self_borrow.data)?;
+        state.serialize_field("negate", &self_borrow.negate)?;
+        state.serialize_field("weight", &self_borrow.weight)?;
+        state.serialize_field("priority", &self_borrow.priority)?;
+        state.serialize_field("children", &children)?;
+        state.end()
+    }
+}
+
+/// Tree-shaped representation of a `Node` that is deserialized before being linked.
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>, U: Deserialize<'de>, K: Deserialize<'de>"))]
+struct NodeRepr<T, U, K> {
+    id: K,
+    op: Option<Op>,
+    decision: Option<U>,
+    data: Option<T>,
+    #[serde(default)]
+    negate: bool,
+    #[serde(default)]
+    weight: Option<f64>,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    children: Vec<NodeRepr<T, U, K>>,
+}
+
+impl<'de, 'a, T, U, K> Deserialize<'de> for DT<'a, T, U, K>
+where
+    T: Deserialize<'de>,
+    U: PartialEq + PartialOrd + Copy + Deserialize<'de>,
+    K: Eq + std::hash::Hash + Clone + std::fmt::Debug + Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let repr = NodeRepr::deserialize(deserializer)?;
+        let hash = Rc::new(RefCell::new(std::collections::HashMap::new()));
+        build(repr, &hash).map_err(D::Error::custom)
+    }
+}
+
+/// Create the `Node` described by `repr` and its children, registering them in `hash`.
+fn build<'a, T, U, K>(
+    repr: NodeRepr<T, U, K>,
+    hash: &HashLink<'a, T, U, K>,
+) -> Result<DT<'a, T, U, K>, AppendError<K>>
+where
+    U: PartialEq + PartialOrd + Copy,
+    K: Eq + std::hash::Hash + Clone,
+{
+    if hash.borrow().contains_key(&repr.id) {
+        return Err(AppendError::DuplicateId(repr.id));
+    }
+    let node = DT::new(
+        repr.id.clone(),
+        repr.op,
+        repr.data,
+        repr.decision,
+        hash.clone(),
+    );
+    {
+        let mut node_borrow = node.0.borrow_mut();
+        node_borrow.negate = repr.negate;
+        node_borrow.weight = repr.weight;
+        node_borrow.priority = repr.priority;
+    }
+    hash.borrow_mut().insert(repr.id, Rc::downgrade(&node.0));
+
+    for child_repr in repr.children {
+        if child_repr.op == Some(Op::Default) && node.default_child().is_some() {
+            return Err(AppendError::DuplicateDefault(node.id()));
+        }
+        let child = build(child_repr, hash)?;
+        child.0.borrow_mut().latest_parent = Some(Rc::downgrade(&node.0));
+        let mut node_borrow = node.0.borrow_mut();
+        node_borrow.latest_child = Some(child.0.clone());
+        node_borrow.children.push(child.0);
+    }
+    Ok(node)
+}
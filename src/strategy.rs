@@ -0,0 +1,106 @@
+//! Pluggable matching strategies for `Traverse::traverse_with`.
+//!
+//! A strategy decides if a child matches by comparing the given input against the
+//! decision of the child, which makes custom routing possible without extending
+//! `PartialOp`. The comparison operators are available as the unit structs of this
+//! module, e.g. `strategy::Greater` matches a child if the input is greater than its
+//! decision, which `Op` and `PartialOp` delegate to as well.
+
+use crate::{Op, PartialOp};
+
+/// Decides if a child matches the input given to `Traverse::traverse_with`.
+pub trait MatchStrategy<U> {
+    /// Returns true if `input` matches the decision `target` of a child.
+    fn matches(&self, input: &U, target: &U) -> bool;
+
+    /// Returns the name recorded in the history of `Traverse` when the strategy matches,
+    /// which is the key counted by `Traverse::op_usage`, e.g. `"Less"`.
+    ///
+    /// Defaults to the name of the implementing type without its path and generic
+    /// arguments.
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().unwrap_or(name)
+    }
+}
+
+macro_rules! comparison_strategy {
+    ($(#[$attr:meta])* $name:ident, $input:ident, $target:ident => $compare:expr) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+        pub struct $name;
+
+        impl<U: PartialOrd> MatchStrategy<U> for $name {
+            fn matches(&self, $input: &U, $target: &U) -> bool {
+                $compare
+            }
+
+            fn name(&self) -> &'static str {
+                stringify!($name)
+            }
+        }
+    };
+}
+
+comparison_strategy!(
+    /// Matches if the input is equal to the decision.
+    Equal, input, target => input == target
+);
+comparison_strategy!(
+    /// Matches if the input is not equal to the decision.
+    NotEqual, input, target => input != target
+);
+comparison_strategy!(
+    /// Matches if the input is greater than the decision.
+    Greater, input, target => input > target
+);
+comparison_strategy!(
+    /// Matches if the input is greater than or equal to the decision.
+    GreaterEqual, input, target => input >= target
+);
+comparison_strategy!(
+    /// Matches if the input is less than the decision.
+    Less, input, target => input < target
+);
+comparison_strategy!(
+    /// Matches if the input is less than or equal to the decision.
+    LessEqual, input, target => input <= target
+);
+
+impl<U: PartialOrd> MatchStrategy<U> for Op {
+    fn matches(&self, input: &U, target: &U) -> bool {
+        match self {
+            Op::Equal => Equal.matches(input, target),
+            Op::Greater => Greater.matches(input, target),
+            Op::GreaterEqual => GreaterEqual.matches(input, target),
+            Op::Less => Less.matches(input, target),
+            Op::LessEqual => LessEqual.matches(input, target),
+            Op::NotEqual => NotEqual.matches(input, target),
+            // Only moved to when nothing else matches
+            Op::Default => false,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        Op::name(self)
+    }
+}
+
+impl<U: PartialOrd> MatchStrategy<U> for PartialOp {
+    fn matches(&self, input: &U, target: &U) -> bool {
+        match self {
+            PartialOp::Equal => Equal.matches(input, target),
+            PartialOp::Greater => Greater.matches(input, target),
+            PartialOp::GreaterEqual => GreaterEqual.matches(input, target),
+            PartialOp::Less => Less.matches(input, target),
+            PartialOp::LessEqual => LessEqual.matches(input, target),
+            // Aggregates select a child among all of them, never a single pair
+            PartialOp::Median | PartialOp::Max | PartialOp::Min => false,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        PartialOp::name(self)
+    }
+}
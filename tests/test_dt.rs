@@ -1,6 +1,9 @@
 #[cfg(test)]
 mod tests {
-    use cdt::{Op, Traverse, DT};
+    use cdt::{
+        decision_tree, strategy, AppendError, AttachError, CoverageReport, MatchStrategy,
+        MergeError, Op, PartialOp, Traverse, DT,
+    };
 
     #[test]
     fn test_dt() {
@@ -24,9 +27,9 @@ mod tests {
             .append("10", "data10", 10, Op::Less);
 
         let mut travel = Traverse::start(tree);
-        println!("{:?}", travel.traverse(&4));
-        println!("{:?}", travel.traverse(&1));
-        println!("{:?}", travel.traverse(&1));
+        println!("{:?}", travel.traverse(&4, PartialOp::Equal));
+        println!("{:?}", travel.traverse(&1, PartialOp::Less));
+        println!("{:?}", travel.traverse(&1, PartialOp::Less));
     }
 
     #[test]
@@ -36,8 +39,8 @@ mod tests {
         tree.append("id", "data", 1, Op::Greater);
 
         let mut travel = Traverse::start(tree);
-        assert!(travel.traverse(&2).is_some());
-        assert!(travel.traverse(&0).is_none());
+        assert!(travel.traverse(&2, PartialOp::Greater).is_some());
+        assert!(travel.traverse(&0, PartialOp::Greater).is_none());
     }
 
     #[test]
@@ -47,7 +50,7 @@ mod tests {
         tree.append("id", "data", 1, Op::Greater);
 
         let mut travel = Traverse::start(tree.clone());
-        assert!(travel.traverse(&2).is_some());
+        assert!(travel.traverse(&2, PartialOp::Greater).is_some());
         assert!(travel.dt().root().unwrap() == tree);
     }
 
@@ -69,10 +72,21 @@ mod tests {
             .append("6", "data6", "f", Op::Greater)
             .append("7", "data7", "g", Op::Greater);
 
-        let mut travel = Traverse::start(tree);
-        assert!(travel.traverse(&"b").unwrap().decision().unwrap() == "b");
-        assert!(travel.traverse(&"c").unwrap().decision().unwrap() == "d");
-        assert!(travel.traverse(&"h").unwrap().decision().unwrap() == "f");
+        let mut travel = Traverse::start(tree.clone());
+        let next = travel.traverse(&"b", PartialOp::Equal);
+        assert!(next.unwrap().decision().unwrap() == "b");
+        let next = travel.traverse(&"c", PartialOp::Less);
+        assert!(next.unwrap().decision().unwrap() == "d");
+        let next = travel.traverse(&"h", PartialOp::Greater);
+        assert!(next.unwrap().decision().unwrap() == "f");
+
+        let travel = Traverse::start(tree);
+        let median = travel.fork().traverse(&"", PartialOp::Median);
+        assert!(median.unwrap().decision().unwrap() == "b");
+        let max = travel.fork().traverse(&"", PartialOp::Max);
+        assert!(max.unwrap().decision().unwrap() == "c");
+        let min = travel.fork().traverse(&"", PartialOp::Min);
+        assert!(min.unwrap().decision().unwrap() == "a");
     }
 
     #[test]
@@ -137,7 +151,1510 @@ mod tests {
             .append("3", Package(c), 3, Op::Equal)
             .append("4", Package(d), 4, Op::Equal);
         let mut travel = Traverse::start(tree);
-        travel.traverse(&2).unwrap().content().unwrap()();
-        travel.traverse(&3).unwrap().content().unwrap()();
+        let next = travel.traverse(&2, PartialOp::Equal).unwrap();
+        next.content().unwrap()();
+        let next = travel.traverse(&3, PartialOp::Equal).unwrap();
+        next.content().unwrap()();
+    }
+
+    #[test]
+    fn test_path_nodes() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal);
+
+        let mut travel = Traverse::start(tree);
+        travel.traverse(&2, PartialOp::Equal);
+        travel.traverse(&3, PartialOp::Equal);
+        let ids: Vec<&str> = travel.path_nodes().iter().map(|node| node.id()).collect();
+        assert_eq!(ids, vec!["root", "2", "3"]);
+    }
+
+    #[test]
+    fn test_decision_table() {
+        let mut tree = DT::init();
+        tree.append("a", "data_a", 1, Op::Equal)
+            .append("b", "data_b", 2, Op::Equal);
+        tree.find("a").unwrap().append("c", "data_c", 3, Op::Equal);
+
+        assert_eq!(
+            tree.to_decision_table(),
+            vec![vec![None, Some(1), Some(3)], vec![None, Some(2), None]]
+        );
+    }
+
+    #[test]
+    fn test_insertion_index() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal);
+        tree.append("4", "data4", 4, Op::Equal);
+
+        let indices: Vec<usize> = ["root", "1", "2", "3", "4"]
+            .iter()
+            .map(|id| tree.find(id).unwrap().insertion_index())
+            .collect();
+        assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_retain_subtree_if() {
+        let mut tree = DT::init();
+        tree.append("keep", "data", 1, Op::Equal)
+            .append("drop", "data", 2, Op::Equal);
+        tree.find("keep")
+            .unwrap()
+            .append("keep_child", "data", 3, Op::Equal);
+        tree.find("drop")
+            .unwrap()
+            .append("drop_child", "data", 4, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("deep", "data", -1, Op::Equal);
+
+        tree.retain_subtree_if(|node| node.decision().is_none_or(|d| d > 0));
+        assert!(tree.len() == 1);
+        assert!(tree.contains("keep_child"));
+        assert!(!tree.contains("drop"));
+        assert!(!tree.contains("deep"));
+        assert!(tree.tree_len() == 3);
+    }
+
+    #[test]
+    fn test_take_path() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal);
+
+        let mut travel = Traverse::start(tree.clone());
+        travel.traverse(&1, PartialOp::Equal);
+        travel.traverse(&2, PartialOp::Equal);
+        travel.traverse(&3, PartialOp::Equal);
+        assert!(travel.stack_depth() == 3);
+
+        let ids: Vec<&str> = travel.take_path().iter().map(|node| node.id()).collect();
+        assert_eq!(ids, vec!["root", "1", "2", "3"]);
+        assert!(travel.stack_depth() == 0);
+        assert!(travel.dt() == tree);
+    }
+
+    #[test]
+    fn test_negate() {
+        let mut tree = DT::init();
+        tree.append("not_greater", "data", 5, Op::Greater)
+            .latest_child()
+            .unwrap()
+            .set_negate(true);
+
+        assert!(Traverse::start(tree.clone()).advance(&3).is_some());
+        assert!(Traverse::start(tree.clone()).advance(&5).is_some());
+        assert!(Traverse::start(tree.clone()).advance(&6).is_none());
+        assert!(Traverse::start(tree.clone())
+            .traverse(&5, PartialOp::Greater)
+            .is_some());
+        assert!(Traverse::start(tree)
+            .traverse(&6, PartialOp::Greater)
+            .is_none());
+    }
+
+    #[test]
+    fn test_descendant_at() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .append("4", "data4", 4, Op::Equal);
+
+        assert!(tree.descendant_at(&[1, 0]).unwrap().id() == "3");
+        assert!(tree.descendant_at(&[]).unwrap() == tree);
+        assert!(tree.descendant_at(&[1, 2]).is_none());
+    }
+
+    #[test]
+    fn test_history_ids() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Less)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Greater);
+
+        let mut travel = Traverse::start(tree);
+        travel.advance(&2);
+        travel.advance(&1);
+        travel.advance(&5);
+        assert_eq!(travel.history_ids(), vec!["root", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_path_weight() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal)
+            .append("3", "data3", 3, Op::Equal);
+        tree.find("1").unwrap().set_weight(1.5);
+        tree.find("2").unwrap().set_weight(2.0);
+        tree.find("3").unwrap().set_weight(10.0);
+
+        let mut travel = Traverse::start(tree);
+        assert!(travel.path_weight() == 0.0);
+        travel.traverse(&1, PartialOp::Equal);
+        travel.traverse(&2, PartialOp::Equal);
+        assert!(travel.path_weight() == 3.5);
+    }
+
+    #[test]
+    fn test_merge_leaves_by() {
+        let mut tree = DT::init();
+        tree.append("1", 10, 1, Op::Equal)
+            .append("2", 20, 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", 11, 3, Op::Equal);
+
+        // Group leaves by the tens digit of their data
+        tree.merge_leaves_by(|data| data / 10, |kept, other| *kept += other);
+        assert!(tree.contains("1"));
+        assert!(!tree.contains("3"));
+        assert!(tree.find("1").unwrap().content().unwrap() == 21);
+        assert!(!tree.find("2").unwrap().has_children());
+    }
+
+    #[test]
+    fn test_pretty_json() {
+        let mut tree = DT::init();
+        tree.append("a", "data\"a\"", 1, Op::Equal);
+
+        assert_eq!(
+            tree.to_json(),
+            "{\"id\":\"root\",\"op\":null,\"decision\":null,\"data\":null,\"children\":[\
+             {\"id\":\"a\",\"op\":\"Equal\",\"decision\":\"1\",\"data\":\"data\\\"a\\\"\",\"children\":[]}]}"
+        );
+        assert_eq!(
+            tree.to_pretty_json(2),
+            r#"{
+  "id": "root",
+  "op": null,
+  "decision": null,
+  "data": null,
+  "children": [
+    {
+      "id": "a",
+      "op": "Equal",
+      "decision": "1",
+      "data": "data\"a\"",
+      "children": []
+    }
+  ]
+}"#
+        );
+    }
+
+    #[test]
+    fn test_contains_cycle() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal);
+        assert!(!tree.contains_cycle());
+    }
+
+    #[test]
+    fn test_sort_children_by_decision() {
+        let mut tree = DT::init();
+        tree.append("3", "data3", 3, Op::Less)
+            .append("1", "data1", 1, Op::Less)
+            .append("2", "data2", 2, Op::Less);
+
+        tree.sort_children_by_decision(true);
+        let decisions: Vec<i32> = (0..tree.len())
+            .map(|index| tree.child_index(index).unwrap().decision().unwrap())
+            .collect();
+        assert_eq!(decisions, vec![1, 2, 3]);
+
+        tree.sort_children_by_decision(false);
+        assert!(tree.first().unwrap().id() == "3");
+    }
+
+    #[test]
+    fn test_replay_decisions() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal);
+
+        let mut travel = Traverse::start(tree.clone());
+        let (all_matched, node) = travel.replay_decisions(&[1, 2, 3], PartialOp::Equal);
+        assert!(all_matched);
+        assert!(node.id() == "3");
+
+        let mut travel = Traverse::start(tree);
+        let (all_matched, node) = travel.replay_decisions(&[1, 2, 4, 3], PartialOp::Equal);
+        assert!(!all_matched);
+        assert!(node.id() == "2");
+    }
+
+    #[test]
+    fn test_count_nodes_where_op() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Less)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .append("4", "data4", 4, Op::Greater)
+            .latest_child()
+            .unwrap()
+            .append("5", "data5", 5, Op::Equal);
+
+        assert!(tree.count_nodes_where_op(&Op::Equal) == 3);
+        assert!(tree.count_nodes_where_op(&Op::Less) == 1);
+        assert!(tree.count_nodes_where_op(&Op::LessEqual) == 0);
+        assert!(tree.find("2").unwrap().count_nodes_where_op(&Op::Equal) == 2);
+    }
+
+    #[test]
+    fn test_decision_tree_macro() {
+        let tree = decision_tree! {
+            "first" => ("banana", true, Op::Equal),
+            "second" => ("apple", false, Op::Equal) {
+                "fourth" => ("red apple", true, Op::Equal),
+                "fifth" => ("green apple", false, Op::Equal) {
+                    "sixth" => ("sour apple", true, Op::Equal)
+                }
+            },
+            "third" => ("orange", false, Op::Equal),
+        };
+
+        assert!(tree.tree_len() == 7);
+        assert!(tree.len() == 3);
+        assert!(tree.find("second").unwrap().len() == 2);
+        let sixth = tree.find("sixth").unwrap();
+        assert!(sixth.latest_parent().unwrap().id() == "fifth");
+        assert!(sixth.content().unwrap() == "sour apple");
+
+        let mut travel = Traverse::start(tree);
+        assert!(travel.traverse(&false, PartialOp::Equal).unwrap().id() == "second");
+        assert!(travel.traverse(&true, PartialOp::Equal).unwrap().id() == "fourth");
+    }
+
+    #[test]
+    fn test_decision_tree_macro_id_evaluated_once() {
+        let evaluated = std::cell::Cell::new(0);
+        let parent_id = || {
+            evaluated.set(evaluated.get() + 1);
+            "parent"
+        };
+        let tree = decision_tree! {
+            parent_id() => ("data", 1, Op::Equal) {
+                "child" => ("data_child", 2, Op::Equal)
+            },
+        };
+
+        assert!(evaluated.get() == 1);
+        assert!(tree.find("child").unwrap().latest_parent().unwrap().id() == "parent");
+    }
+
+    #[test]
+    fn test_decision_tree_macro_empty() {
+        let tree: DT<&str, i32> = decision_tree! {};
+        assert!(tree.tree_len() == 1);
+        assert!(tree.is_root());
+    }
+
+    #[test]
+    fn test_fork() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal)
+            .append("3", "data3", 3, Op::Equal);
+
+        let mut travel = Traverse::start(tree);
+        travel.traverse(&1, PartialOp::Equal);
+        let mut fork = travel.fork();
+        assert!(fork.traverse(&3, PartialOp::Equal).unwrap().id() == "3");
+
+        assert!(travel.dt().id() == "1");
+        assert_eq!(travel.history_ids(), vec!["root", "1"]);
+        assert_eq!(fork.history_ids(), vec!["root", "1", "3"]);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut tree = DT::init();
+        tree.append("first", "banana", true, Op::Equal)
+            .append("second", "apple", false, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("third", "red apple", true, Op::Equal);
+
+        assert_eq!(
+            format!("{}", tree),
+            "root\n  first: banana (true)\n  second: apple (false)\n    third: red apple (true)"
+        );
+        assert_eq!(
+            format!("{}", tree.find("third").unwrap()),
+            "third: red apple (true)"
+        );
+    }
+
+    #[test]
+    fn test_id_skeleton() {
+        let mut tree = DT::init();
+        tree.append("a", "data_a", 1, Op::Equal)
+            .append("b", "data_b", 2, Op::Equal);
+        tree.find("a").unwrap().append("c", "data_c", 3, Op::Equal);
+
+        assert_eq!(tree.id_skeleton(), "root(a(c),b)");
+        assert_eq!(tree.find("a").unwrap().id_skeleton(), "a(c)");
+
+        let copy = tree.deep_clone();
+        assert!(copy != tree);
+        assert_eq!(copy.id_skeleton(), tree.id_skeleton());
+        assert!(copy.tree_len() == 4);
+        assert!(copy.find("c").unwrap() != tree.find("c").unwrap());
+    }
+
+    #[test]
+    fn test_iter_dfs_bfs() {
+        let mut tree = DT::init();
+        tree.append("first", "banana", true, Op::Equal)
+            .append("second", "apple", false, Op::Equal)
+            .append("third", "orange", false, Op::Equal);
+        tree.find("second")
+            .unwrap()
+            .append("fourth", "red apple", true, Op::Equal)
+            .append("fifth", "green apple", false, Op::Equal);
+
+        let dfs: Vec<&str> = tree.iter_dfs().map(|node| node.id()).collect();
+        assert_eq!(
+            dfs,
+            vec!["root", "first", "second", "fourth", "fifth", "third"]
+        );
+
+        let bfs: Vec<&str> = tree.iter_bfs().map(|node| node.id()).collect();
+        assert_eq!(
+            bfs,
+            vec!["root", "first", "second", "third", "fourth", "fifth"]
+        );
+
+        // No borrow is held between iterations
+        for mut node in tree.iter_dfs() {
+            if node.id() == "first" {
+                node.append("sixth", "banana peel", true, Op::Equal);
+            }
+        }
+        assert!(tree.contains("sixth"));
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut tree = DT::init();
+        tree.append("low", "data_low", 1, Op::GreaterEqual).append(
+            "high",
+            "data_high",
+            2,
+            Op::GreaterEqual,
+        );
+
+        let mut travel = Traverse::start(tree.clone());
+        assert!(
+            travel
+                .fork()
+                .traverse(&3, PartialOp::GreaterEqual)
+                .unwrap()
+                .id()
+                == "low"
+        );
+
+        tree.find("high").unwrap().set_priority(1);
+        assert!(
+            travel
+                .fork()
+                .traverse(&3, PartialOp::GreaterEqual)
+                .unwrap()
+                .id()
+                == "high"
+        );
+        assert!(travel.fork().advance(&3).unwrap().id() == "high");
+        // Falls through to the lower priority child when the higher one fails
+        assert!(travel.traverse(&1, PartialOp::GreaterEqual).unwrap().id() == "low");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal);
+        assert!(tree.tree_len() == 5);
+
+        assert!(tree.remove("2"));
+        assert!(tree.len() == 1);
+        assert!(tree.tree_len() == 2);
+        assert!(!tree.contains("2"));
+        assert!(!tree.contains("3"));
+        assert!(!tree.contains("4"));
+
+        assert!(!tree.remove("2"));
+        assert!(!tree.remove("root"));
+        assert!(tree.contains("root"));
+    }
+
+    #[test]
+    fn test_append_to_removed() {
+        let mut tree = DT::init();
+        tree.append("a", "data_a", 1, Op::Equal)
+            .append("y", "data_y", 2, Op::Equal);
+        tree.find("a").unwrap().append("b", "data_b", 3, Op::Equal);
+
+        let mut old_a = tree.find("a").unwrap();
+        assert!(tree.remove("a"));
+        old_a.append("ghost", "data_ghost", 4, Op::Equal);
+
+        assert!(!tree.contains("ghost"));
+        assert!(tree.tree_len() == 2);
+        assert!(tree.validate_no_orphans() == Ok(()));
+        assert!(old_a.contains("ghost"));
+        assert!(old_a.contains("b"));
+        assert!(old_a.tree_len() == 3);
+        assert!(old_a.find("y").is_none());
+        assert!(old_a.validate_no_orphans() == Ok(()));
+    }
+
+    #[test]
+    fn test_to_indented_lines() {
+        let mut tree = DT::init();
+        tree.append("a", "data_a", 1, Op::Equal)
+            .append("b", "data_b", 2, Op::Equal);
+        tree.find("a")
+            .unwrap()
+            .append("c", "data_c", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("d", "data_d", 4, Op::Equal);
+
+        let lines = tree.to_indented_lines();
+        assert!(lines.len() == tree.tree_len());
+        let depths: Vec<usize> = lines
+            .iter()
+            .map(|line| line.len() - line.trim_start().len())
+            .collect();
+        assert_eq!(depths, vec![0, 2, 4, 6, 2]);
+        assert_eq!(lines[3], "      d: data_d (4)");
+    }
+
+    #[test]
+    fn test_try_append() {
+        let mut tree = DT::init();
+        assert!(tree.try_append("1", "data1", 1, Op::Equal).is_ok());
+        let tree_len = tree.tree_len();
+
+        assert_eq!(
+            tree.try_append("1", "data2", 2, Op::Equal),
+            Err(AppendError::DuplicateId("1"))
+        );
+        assert!(tree.tree_len() == tree_len);
+        assert!(tree.len() == 1);
+        assert!(tree.find("1").unwrap().content().unwrap() == "data1");
+    }
+
+    #[test]
+    fn test_content_ref() {
+        let mut tree: DT<String, i32> = DT::init();
+        tree.append("1", String::from("banana"), 1, Op::Equal);
+
+        let node = tree.find("1").unwrap();
+        assert!(*node.content_ref().unwrap() == "banana");
+        assert!(node.content_ref().unwrap().len() == 6);
+        assert!(tree.content_ref().is_none());
+    }
+
+    #[test]
+    fn test_init_with_data() {
+        let mut tree = DT::init_with_data(String::from("metadata"));
+        tree.append("1", String::from("data1"), 1, Op::Equal);
+
+        let root = tree.find("1").unwrap().root().unwrap();
+        assert!(*root.content_ref().unwrap() == "metadata");
+        assert!(root.decision().is_none());
+        assert!(tree.tree_len() == 2);
+    }
+
+    #[test]
+    fn test_set_content() {
+        let mut tree = DT::init();
+        tree.append("1", 0, 1, Op::Equal);
+
+        let node = tree.find("1").unwrap();
+        node.set_content(5);
+        assert!(node.content().unwrap() == 5);
+
+        node.with_content_mut(|count| *count += 1);
+        assert!(node.content().unwrap() == 6);
+
+        tree.with_content_mut(|count| *count += 1);
+        assert!(tree.content().is_none());
+    }
+
+    #[test]
+    fn test_for_each_child() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .append("3", "data3", 3, Op::Equal);
+
+        let mut sum = 0;
+        tree.for_each_child(|child| sum += child.decision().unwrap());
+        assert!(sum == 6);
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut tree = DT::init();
+        tree.append("small", "data_small", 5, Op::Less)
+            .append("large", "data_large", 5, Op::GreaterEqual)
+            .latest_child()
+            .unwrap()
+            .append("exact", "data_exact", 7, Op::Equal);
+
+        let mut travel = Traverse::start(tree.clone());
+        assert!(travel.advance(&3).unwrap().id() == "small");
+        assert!(travel.advance(&3).is_none());
+
+        let mut travel = Traverse::start(tree);
+        assert!(travel.advance(&7).unwrap().id() == "large");
+        assert!(travel.advance(&7).unwrap().id() == "exact");
+    }
+
+    #[test]
+    fn test_truncate_at_depth() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("5", "data5", 5, Op::Equal);
+
+        tree.truncate_at_depth(2);
+        // Every remaining node reaches the root within two steps
+        assert!(tree.iter_dfs().all(|node| node.back(3).is_none()));
+        assert!(tree.contains("3"));
+        assert!(!tree.find("3").unwrap().has_children());
+        assert!(!tree.contains("4"));
+        assert!(!tree.contains("5"));
+        assert!(tree.tree_len() == 4);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut tree = DT::init();
+        tree.append("first", "banana", true, Op::Equal)
+            .append("second", "apple", false, Op::Equal)
+            .append("third", "orange", false, Op::Equal);
+        tree.find("second")
+            .unwrap()
+            .append("fourth", "red apple", true, Op::Equal)
+            .append("fifth", "green apple", false, Op::Equal);
+
+        let mut travel = Traverse::start(tree.clone());
+        let ids: Vec<&str> = travel
+            .run(&false, PartialOp::Equal)
+            .iter()
+            .map(|node| node.id())
+            .collect();
+        assert_eq!(ids, vec!["second", "fifth"]);
+        assert!(travel.dt().id() == "fifth");
+
+        let mut travel = Traverse::start(tree.clone());
+        assert!(travel.run(&false, PartialOp::Greater).is_empty());
+        assert!(travel.dt() == tree);
+    }
+
+    #[test]
+    fn test_op_usage() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 5, Op::Less)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 2, Op::Greater)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal);
+
+        let mut travel = Traverse::start(tree);
+        travel.advance(&1);
+        travel.advance(&3);
+        travel.traverse(&3, PartialOp::Greater);
+        travel.traverse(&4, PartialOp::Equal);
+
+        let usage = travel.op_usage();
+        assert!(usage.len() == 3);
+        assert!(usage["Equal"] == 2);
+        assert!(usage["Less"] == 1);
+        assert!(usage["Greater"] == 1);
+    }
+
+    #[test]
+    fn test_append_default() {
+        let mut tree = DT::init();
+        tree.append_default("default", "data_default").unwrap();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal);
+
+        let travel = Traverse::start(tree.clone());
+        assert!(travel.fork().traverse(&2, PartialOp::Equal).unwrap().id() == "2");
+        assert!(travel.fork().traverse(&3, PartialOp::Equal).unwrap().id() == "default");
+        assert!(travel.fork().advance(&1).unwrap().id() == "1");
+        assert!(travel.fork().advance(&3).unwrap().id() == "default");
+
+        let tree_len = tree.tree_len();
+        assert_eq!(
+            tree.append_default("other", "data_other"),
+            Err(AppendError::DuplicateDefault("root"))
+        );
+        assert!(tree.tree_len() == tree_len);
+    }
+
+    #[test]
+    fn test_append_with_default_op() {
+        let mut tree = DT::init();
+        tree.append("default", "data_default", 1, Op::Default)
+            .append("1", "data1", 2, Op::Equal);
+        assert_eq!(
+            tree.try_append("other", "data_other", 1, Op::Default),
+            Err(AppendError::DuplicateDefault("root"))
+        );
+        assert!(!tree.contains("other"));
+
+        // The default child is skipped even though it has a decision
+        let travel = Traverse::start(tree.clone());
+        assert!(travel.fork().traverse(&2, PartialOp::Equal).unwrap().id() == "1");
+        assert!(travel.fork().traverse(&1, PartialOp::Equal).unwrap().id() == "default");
+        assert!(travel.fork().traverse(&0, PartialOp::Min).unwrap().id() == "1");
+        assert!(travel.fork().advance(&1).unwrap().id() == "default");
+        assert!(travel.matches(&1, PartialOp::Equal).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not allowed to append more than one default child to a node.")]
+    fn test_append_duplicate_default() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Default)
+            .append("2", "data2", 2, Op::Default);
+    }
+
+    #[test]
+    fn test_set_op_recursive_default() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal)
+            .append("3", "data3", 3, Op::Equal);
+
+        assert_eq!(
+            tree.set_op_recursive(Op::Default),
+            Err(AppendError::DuplicateDefault("1"))
+        );
+        assert!(tree.count_nodes_where_op(&Op::Default) == 0);
+
+        tree.remove("3");
+        tree.set_op_recursive(Op::Default).unwrap();
+        assert!(tree.count_nodes_where_op(&Op::Default) == 2);
+
+        let mut travel = Traverse::start(tree);
+        assert!(travel.traverse(&5, PartialOp::Equal).unwrap().id() == "1");
+        assert!(travel.traverse(&1, PartialOp::Equal).unwrap().id() == "2");
+    }
+
+    #[test]
+    fn test_depth_height() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal);
+
+        assert!(tree.depth() == 0);
+        assert!(tree.find("1").unwrap().depth() == 1);
+        assert!(tree.find("4").unwrap().depth() == 3);
+        assert!(tree.height() == 3);
+        assert!(tree.find("3").unwrap().height() == 1);
+        assert!(tree.find("1").unwrap().height() == 0);
+    }
+
+    #[test]
+    fn test_graft_clone_of() {
+        let mut tree = DT::init();
+        tree.append("template", "data", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("leaf", "data_leaf", 2, Op::Equal);
+        tree.append("parent", "data_parent", 3, Op::Equal);
+
+        let mut parent = tree.find("parent").unwrap();
+        let copy = parent
+            .graft_clone_of("template", |id| match id {
+                "template" => "template_copy",
+                "leaf" => "leaf_copy",
+                id => id,
+            })
+            .unwrap();
+        assert!(copy.id() == "template_copy");
+        assert!(tree.tree_len() == 6);
+        let leaf_copy = tree.find("leaf_copy").unwrap();
+        assert!(leaf_copy.latest_parent().unwrap().latest_parent().unwrap() == parent);
+        assert!(leaf_copy != tree.find("leaf").unwrap());
+
+        assert_eq!(
+            parent.graft_clone_of("missing", |id| id),
+            Err(MergeError::MissingTemplate("missing"))
+        );
+        assert_eq!(
+            parent.graft_clone_of("template", |id| id),
+            Err(MergeError::DuplicateId("template"))
+        );
+        assert!(tree.tree_len() == 6);
+    }
+
+    #[test]
+    fn test_graft_clone_of_duplicate_default() {
+        let mut tree = DT::init();
+        tree.append("template", "data", 1, Op::Default);
+
+        assert_eq!(
+            tree.graft_clone_of("template", |_| "copy"),
+            Err(MergeError::DuplicateDefault("root"))
+        );
+        assert!(tree.tree_len() == 2);
+    }
+
+    #[test]
+    fn test_not_equal_and_default_op() {
+        let mut tree = DT::init();
+        tree.append("not_one", "data_not_one", 1, Op::NotEqual)
+            .append_default("default", "data_default")
+            .unwrap();
+
+        let travel = Traverse::start(tree);
+        assert!(travel.fork().advance(&2).unwrap().id() == "not_one");
+        assert!(travel.fork().advance(&1).unwrap().id() == "default");
+        let mut travel = travel.fork();
+        travel.advance(&1);
+        assert!(travel.op_usage()["Default"] == 1);
+    }
+
+    #[test]
+    fn test_attach() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal);
+
+        let mut fragment = DT::init();
+        fragment
+            .append("a", "data_a", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("b", "data_b", 4, Op::Equal)
+            .append("c", "data_c", 5, Op::Equal);
+
+        let mut node = tree.find("2").unwrap();
+        node.attach(fragment.find("a").unwrap()).unwrap();
+        assert!(tree.tree_len() == 6);
+        assert!(node.len() == 1);
+        assert!(tree.find("c").unwrap().root().unwrap() == tree);
+        assert!(tree.find("a").unwrap().latest_parent().unwrap() == node);
+        assert!(fragment.tree_len() == 1);
+        assert!(!fragment.has_children());
+
+        let mut travel = Traverse::start(tree.clone());
+        travel.replay_decisions(&[2, 3, 5], PartialOp::Equal);
+        assert!(travel.dt().id() == "c");
+
+        let mut other = DT::init();
+        other.append("b", "data_b", 6, Op::Equal);
+        assert_eq!(
+            node.attach(other.find("b").unwrap()),
+            Err(AttachError::DuplicateId("b"))
+        );
+        assert!(tree.tree_len() == 6);
+        assert!(other.tree_len() == 2);
+        assert!(other.len() == 1);
+    }
+
+    #[test]
+    fn test_attach_removed_with_reused_id() {
+        let mut tree = DT::init();
+        tree.append("a", "data_a", 1, Op::Equal);
+        let old_a = tree.find("a").unwrap();
+        assert!(tree.remove("a"));
+        tree.append("a", "new", 2, Op::Equal);
+
+        let mut other = DT::init();
+        other.attach(old_a).unwrap();
+        assert!(tree.contains("a"));
+        assert!(tree.find("a").unwrap().content() == Some("new"));
+        assert!(tree.validate_no_orphans() == Ok(()));
+        assert!(other.find("a").unwrap().content() == Some("data_a"));
+        assert!(other.tree_len() == 2);
+    }
+
+    #[test]
+    fn test_attach_would_cycle() {
+        let mut tree = DT::init();
+        tree.append("a", "data_a", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("b", "data_b", 2, Op::Equal);
+
+        let mut a = tree.find("a").unwrap();
+        let mut b = tree.find("b").unwrap();
+        assert!(tree.remove("a"));
+        assert!(b.attach(a.clone()) == Err(AttachError::WouldCycle("a")));
+        assert!(a.attach(a.clone()) == Err(AttachError::WouldCycle("a")));
+        assert!(!a.contains_cycle());
+        assert!(b.latest_parent().unwrap() == a);
+        assert!(a.latest_child().unwrap() == b);
+    }
+
+    #[test]
+    fn test_attach_duplicate_default() {
+        let mut tree = DT::init();
+        tree.append("a", "data_a", 1, Op::Default);
+        let mut other = DT::init_with_id("other");
+        other.append("b", "data_b", 2, Op::Default);
+
+        let b = other.find("b").unwrap();
+        assert!(tree.attach(b.clone()) == Err(AttachError::DuplicateDefault("root")));
+        assert!(b.latest_parent().unwrap() == other);
+        assert!(!tree.contains("b"));
+        assert!(other.contains("b"));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_to_yaml() {
+        let mut tree = DT::init();
+        tree.append("a", "data_a", 1, Op::Equal)
+            .append("b", "data_b", 2, Op::Less);
+        tree.find("a").unwrap().append("c", "data_c", 3, Op::Equal);
+
+        let yaml = tree.to_yaml();
+        let lines: Vec<&str> = yaml.lines().collect();
+        assert!(lines.contains(&"id: \"root\""));
+        assert!(lines.contains(&"  - id: \"a\""));
+        assert!(lines.contains(&"      - id: \"c\""));
+        assert!(lines.contains(&"  - id: \"b\""));
+        assert!(lines.contains(&"    op: \"Less\""));
+        assert!(lines.contains(&"        decision: \"3\""));
+        assert!(lines.contains(&"        children: []"));
+        assert!(lines.len() == 4 * 5);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut tree = DT::init();
+        tree.append("first", "banana", true, Op::Equal)
+            .append("second", "apple", false, Op::Equal)
+            .append("third", "orange", false, Op::Equal);
+        tree.find("second")
+            .unwrap()
+            .append("fourth", "red apple", true, Op::Equal)
+            .append("fifth", "green apple", false, Op::Equal);
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let copy: DT<&str, bool> = serde_json::from_str(&json).unwrap();
+        assert!(copy != tree);
+        assert!(copy.tree_len() == tree.tree_len());
+        assert_eq!(copy.id_skeleton(), tree.id_skeleton());
+        let decisions = |tree: &DT<&str, bool>| -> Vec<Option<bool>> {
+            tree.iter_dfs().map(|node| node.decision()).collect()
+        };
+        assert_eq!(decisions(&copy), decisions(&tree));
+        let fifth = copy.find("fifth").unwrap();
+        assert!(fifth.latest_parent().unwrap().id() == "second");
+        assert!(fifth.root().unwrap() == copy);
+
+        let duplicate = json.replace("\"fifth\"", "\"first\"");
+        assert!(serde_json::from_str::<DT<&str, bool>>(&duplicate).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_duplicate_default() {
+        let mut tree = DT::init();
+        tree.append("first", "banana", true, Op::Equal)
+            .append("second", "apple", false, Op::Equal);
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let defaults = json.replace("\"Equal\"", "\"Default\"");
+        assert!(defaults != json);
+        let error = serde_json::from_str::<DT<&str, bool>>(&defaults).unwrap_err();
+        assert!(error.to_string().contains("already has a default child"));
+    }
+
+    #[test]
+    fn test_assert_reached() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal);
+
+        let mut travel = Traverse::start(tree);
+        travel.assert_reached("root");
+        travel.replay_decisions(&[1, 2], PartialOp::Equal);
+        assert!(travel.current_id() == "2");
+        travel.assert_reached("2");
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected to reach \"1\", but the current node is \"root\".")]
+    fn test_assert_reached_wrong() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal);
+
+        let mut travel = Traverse::start(tree);
+        travel.traverse(&2, PartialOp::Equal);
+        travel.assert_reached("1");
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 1, Op::Equal)
+            .append("3", "data3", 2, Op::Equal)
+            .append("4", "data4", 1, Op::Equal);
+
+        let travel = Traverse::start(tree.clone());
+        fn ids<'a>(nodes: Vec<DT<'a, &str, i32>>) -> Vec<&'a str> {
+            nodes.iter().map(|node| node.id()).collect()
+        }
+        assert_eq!(
+            ids(travel.matches(&1, PartialOp::Equal)),
+            vec!["1", "2", "4"]
+        );
+        assert_eq!(
+            ids(travel.matches(&2, PartialOp::Greater)),
+            vec!["1", "2", "4"]
+        );
+        assert_eq!(ids(travel.matches(&0, PartialOp::Max)), vec!["3"]);
+        assert!(travel.matches(&3, PartialOp::Equal).is_empty());
+        assert!(travel.dt() == tree);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut tree = DT::init();
+        tree.append("first", "banana", true, Op::Equal)
+            .append("second", "apple", false, Op::Equal);
+        tree.find("second")
+            .unwrap()
+            .append("fourth", "red apple", true, Op::Equal)
+            .append("fifth", "green apple", false, Op::Equal);
+
+        let report = tree.coverage(
+            &[vec![true], vec![false, false], vec![false]],
+            PartialOp::Equal,
+        );
+        assert_eq!(
+            report,
+            CoverageReport {
+                reached: vec!["first", "fifth"],
+                unreached: vec!["fourth"],
+            }
+        );
+    }
+
+    #[test]
+    fn test_traverse_back_reset() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal);
+
+        let mut travel = Traverse::start(tree.clone());
+        assert!(travel.back().is_none());
+        travel.replay_decisions(&[1, 2], PartialOp::Equal);
+        assert!(travel.back().unwrap().id() == "1");
+        assert!(travel.back().unwrap().id() == "root");
+        assert!(travel.back().is_none());
+
+        travel.replay_decisions(&[1, 2], PartialOp::Equal);
+        travel.reset();
+        assert!(travel.dt() == tree);
+        assert!(travel.stack_depth() == 0);
+    }
+
+    #[test]
+    fn test_set_op_recursive() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Less)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Greater);
+
+        tree.find("2")
+            .unwrap()
+            .set_op_recursive(Op::GreaterEqual)
+            .unwrap();
+        assert!(tree.find("2").unwrap().op() == Some(Op::Less));
+        assert!(tree.find("3").unwrap().op() == Some(Op::GreaterEqual));
+        assert!(tree.find("4").unwrap().op() == Some(Op::GreaterEqual));
+        assert!(tree.find("1").unwrap().op() == Some(Op::Equal));
+
+        tree.set_op_recursive(Op::LessEqual).unwrap();
+        assert!(tree.op().is_none());
+        assert!(tree.count_nodes_where_op(&Op::LessEqual) == 4);
+    }
+
+    #[test]
+    fn test_generic_key() {
+        let mut tree: DT<&str, i32, u32> = DT::init_with_id(0);
+        tree.append(1, "data1", 1, Op::Equal)
+            .append(2, "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append(3, "data3", 3, Op::Equal);
+
+        assert!(tree.contains(&3));
+        assert!(!tree.contains(&4));
+        assert!(tree.find(&3).unwrap().latest_parent().unwrap().id() == 2);
+        assert!(tree.find(&3).unwrap().root().unwrap() == tree);
+        assert!(tree.try_append(1, "copy", 1, Op::Equal) == Err(AppendError::DuplicateId(1)));
+
+        assert!(tree.remove(&2));
+        assert!(!tree.contains(&3));
+        assert!(!tree.remove(&0));
+
+        let mut travel = Traverse::start(tree);
+        travel.traverse(&1, PartialOp::Equal);
+        assert!(travel.history_ids() == vec![0, 1]);
+    }
+
+    #[test]
+    fn test_can_reach() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal);
+        tree.find("1")
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal);
+
+        let mut travel = Traverse::start(tree.clone());
+        assert!(travel.can_reach("4"));
+        assert!(travel.can_reach("2"));
+
+        travel.traverse(&1, PartialOp::Equal);
+        assert!(travel.can_reach("1"));
+        assert!(travel.can_reach("4"));
+        assert!(!travel.can_reach("2"));
+        assert!(!travel.can_reach("root"));
+        assert!(!travel.can_reach("missing"));
+    }
+
+    #[test]
+    fn test_path_to_root() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal);
+
+        let leaf = tree.find("4").unwrap();
+        let ids: Vec<&str> = leaf.path_to_root().iter().map(|node| node.id()).collect();
+        assert!(ids == vec!["4", "3", "2", "1", "root"]);
+
+        let ancestors: Vec<&str> = leaf.ancestors().map(|node| node.id()).collect();
+        assert!(ancestors == vec!["3", "2", "1", "root"]);
+        assert!(tree.ancestors().next().is_none());
+        assert!(tree.path_to_root() == vec![tree.clone()]);
+    }
+
+    #[test]
+    fn test_export_rules_compact() {
+        let mut tree = DT::init();
+        tree.append("a", "dataA", 1, Op::Equal)
+            .append("e", "dataE", 5, Op::Greater);
+        tree.find("a")
+            .unwrap()
+            .append("b", "dataB", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("c", "dataC", 3, Op::Equal)
+            .append("d", "dataD", 4, Op::LessEqual);
+        tree.find("d").unwrap().set_negate(true);
+
+        assert!(
+            tree.export_rules()
+                == vec![
+                    "a == 1 => dataA",
+                    "  b == 2 => dataB",
+                    "    c == 3 => dataC",
+                    "    NOT d <= 4 => dataD",
+                    "e > 5 => dataE",
+                ]
+        );
+        assert!(
+            tree.export_rules_compact()
+                == vec![
+                    "a == 1 AND b == 2 => dataB",
+                    "  c == 3 => dataC",
+                    "  NOT d <= 4 => dataD",
+                    "e > 5 => dataE",
+                ]
+        );
+    }
+
+    #[test]
+    fn test_find_by() {
+        let mut tree = DT::init();
+        tree.append("1", String::from("banana"), 1, Op::Equal)
+            .append("2", String::from("apple"), 2, Op::Equal);
+        tree.find("2")
+            .unwrap()
+            .append("3", String::from("red apple"), 3, Op::Equal)
+            .append("4", String::from("pear"), 4, Op::Equal);
+
+        let found = tree.find_by(|data| data.contains("apple")).unwrap();
+        assert!(found.id() == "2");
+
+        let ids: Vec<&str> = tree
+            .find_all_by(|data| data.contains("apple"))
+            .iter()
+            .map(|node| node.id())
+            .collect();
+        assert!(ids == vec!["2", "3"]);
+        assert!(tree.find_by(|data| data.is_empty()).is_none());
+        assert!(tree
+            .find("1")
+            .unwrap()
+            .find_all_by(|data| data.contains("apple"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_reset_and_follow() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Less);
+        tree.find("2")
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal);
+
+        let mut travel = Traverse::start(tree.find("1").unwrap());
+        let node = travel.reset_and_follow(&["2", "3", "4"]).unwrap();
+        assert!(node.id() == "4");
+        assert!(travel.current_id() == "4");
+        assert!(travel.history_ids() == vec!["root", "2", "3", "4"]);
+        assert!(travel.op_usage().get("Less") == Some(&1));
+
+        assert!(travel.reset_and_follow(&["2", "4"]).is_none());
+        assert!(travel.history_ids() == vec!["root", "2"]);
+    }
+
+    #[test]
+    fn test_depth_profile() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .append("3", "data3", 3, Op::Equal);
+        tree.find("1")
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal)
+            .append("5", "data5", 5, Op::Equal);
+        tree.find("5").unwrap().append("6", "data6", 6, Op::Equal);
+
+        assert!(tree.depth_profile() == vec![1, 3, 2, 1]);
+        assert!(tree.find("1").unwrap().depth_profile() == vec![1, 2, 1]);
+        assert!(tree.find("6").unwrap().depth_profile() == vec![1]);
+    }
+
+    #[test]
+    fn test_traverse_with() {
+        struct SameParity;
+
+        impl MatchStrategy<i32> for SameParity {
+            fn matches(&self, input: &i32, target: &i32) -> bool {
+                input % 2 == target % 2
+            }
+        }
+
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal);
+        tree.find("2")
+            .unwrap()
+            .append("3", "data3", 3, Op::Equal)
+            .append("4", "data4", 8, Op::Equal);
+
+        let mut travel = Traverse::start(tree);
+        assert!(travel.traverse_with(&6, &SameParity).unwrap().id() == "2");
+        assert!(travel.traverse_with(&5, &strategy::Less).unwrap().id() == "4");
+        assert!(travel.traverse_with(&1, &strategy::Equal).is_none());
+        assert!(travel.history_ids() == vec!["root", "2", "4"]);
+        assert!(travel.op_usage().get("SameParity") == Some(&1));
+        assert!(travel.op_usage().get("Less") == Some(&1));
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let paths = vec![
+            vec!["root", "1", "2", "3"],
+            vec!["root", "1", "4"],
+            vec!["root", "1", "2", "5"],
+        ];
+        assert!(DT::<&str, i32>::common_prefix(&paths) == vec!["root", "1"]);
+        assert!(DT::<&str, i32>::common_prefix(&paths[..1]) == paths[0]);
+        assert!(DT::<&str, i32>::common_prefix(&[vec!["root"], vec!["1"]]).is_empty());
+        assert!(DT::<&str, i32>::common_prefix(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_retry() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal);
+
+        let mut travel = Traverse::start(tree);
+        assert!(travel.retry(&1, PartialOp::Equal).is_none());
+        assert!(travel.stack_depth() == 0);
+
+        assert!(travel.traverse(&1, PartialOp::Equal).unwrap().id() == "1");
+        assert!(travel.retry(&2, PartialOp::Equal).unwrap().id() == "2");
+        assert!(travel.history_ids() == vec!["root", "2"]);
+
+        assert!(travel.retry(&3, PartialOp::Equal).is_none());
+        assert!(travel.current_id() == "2");
+        assert!(travel.history_ids() == vec!["root", "2"]);
+    }
+
+    #[test]
+    fn test_validate_no_orphans_from_orphan() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal);
+        let child = tree.find("1").unwrap();
+        assert!(child.validate_no_orphans() == Ok(()));
+
+        drop(tree);
+        assert!(!child.has_parent());
+        assert!(child.validate_no_orphans() == Err(vec!["1"]));
+    }
+
+    #[test]
+    fn test_children_matching() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 5, Op::Equal)
+            .append("3", "data3", 3, Op::Equal)
+            .append_default("4", "data4")
+            .unwrap();
+
+        let ids: Vec<&str> = tree
+            .children_matching(&3, PartialOp::LessEqual)
+            .iter()
+            .map(|node| node.id())
+            .collect();
+        assert!(ids == vec!["2", "3"]);
+        assert!(tree.children_matching(&0, PartialOp::Greater).is_empty());
+        assert!(tree.children_matching(&0, PartialOp::Max)[0].id() == "2");
+    }
+
+    #[test]
+    fn test_visited_decision_histogram() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("2", "data2", 2, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append("3", "data3", 1, Op::Equal);
+
+        let mut travel = Traverse::start(tree);
+        assert!(travel.visited_decision_histogram().is_empty());
+
+        travel.replay_decisions(&[1, 2, 1], PartialOp::Equal);
+        let histogram = travel.visited_decision_histogram();
+        assert!(histogram.len() == 2);
+        assert!(histogram.get(&1) == Some(&2));
+        assert!(histogram.get(&2) == Some(&1));
+    }
+
+    #[test]
+    fn test_clone_with_ids() {
+        let mut template: DT<&str, i32, String> = DT::init_with_id(String::from("root"));
+        template
+            .append(String::from("1"), "data1", 1, Op::Equal)
+            .latest_child()
+            .unwrap()
+            .append(String::from("2"), "data2", 2, Op::Equal);
+
+        let first = template.clone_with_ids(|id| format!("{}_a", id));
+        let second = template.clone_with_ids(|id| format!("{}_b", id));
+        assert!(first.id() == "root_a");
+        assert!(first.tree_len() == 3);
+        assert!(first.find("2_a").unwrap().content() == Some("data2"));
+        assert!(!first.contains("2_b"));
+        assert!(second.find("2_b").unwrap().latest_parent().unwrap().id() == "1_b");
+
+        first.find("2_a").unwrap().set_content("changed");
+        assert!(second.find("2_b").unwrap().content() == Some("data2"));
+        assert!(template.find("2").unwrap().content() == Some("data2"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not allowed to map two ids to the same id.")]
+    fn test_clone_with_ids_collision() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal);
+        tree.clone_with_ids(|_| "same");
+    }
+
+    #[test]
+    fn test_trim_to_reachable() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal)
+            .append("3", "data3", 3, Op::Equal);
+        tree.find("1")
+            .unwrap()
+            .append("4", "data4", 4, Op::Equal)
+            .append("5", "data5", 5, Op::Equal);
+        tree.find("2").unwrap().append("6", "data6", 6, Op::Equal);
+
+        tree.trim_to_reachable(&[vec![1, 5], vec![2]], PartialOp::Equal);
+        assert!(tree.id_skeleton() == "root(1(5),2)");
+        assert!(tree.tree_len() == 4);
+        assert!(!tree.contains("3"));
+        assert!(!tree.contains("6"));
+    }
+
+    #[test]
+    fn test_debug_key() {
+        #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+        enum Key {
+            Root,
+            Child,
+        }
+
+        let mut tree: DT<&str, i32, Key> = DT::init_with_id(Key::Root);
+        tree.append(Key::Child, "data", 1, Op::Equal);
+        assert!(
+            format!("{:?}", tree.find(&Key::Child).unwrap()) == "Child(Some(\"data\"), Some(1))"
+        );
+    }
+
+    #[test]
+    fn test_clone_keeps_insertion_order() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal);
+        // Appended last, but visited before "2" in depth-first order
+        tree.find("1").unwrap().append("3", "data3", 3, Op::Equal);
+
+        fn order<'a>(tree: &DT<'a, &str, i32>) -> Vec<&'a str> {
+            let mut nodes: Vec<DT<&str, i32>> = tree.iter_dfs().collect();
+            nodes.sort_by_key(DT::insertion_index);
+            nodes.iter().map(|node| node.id()).collect()
+        }
+        assert!(order(&tree) == vec!["root", "1", "2", "3"]);
+        assert!(order(&tree.deep_clone()) == vec!["root", "1", "2", "3"]);
+
+        let copy = tree.clone_with_ids(|id| match id {
+            "root" => "root_copy",
+            "1" => "1_copy",
+            "2" => "2_copy",
+            _ => "3_copy",
+        });
+        assert!(order(&copy) == vec!["root_copy", "1_copy", "2_copy", "3_copy"]);
+        assert!(copy.insertion_index() > tree.find("3").unwrap().insertion_index());
+    }
+
+    #[test]
+    fn test_op_as_strategy() {
+        let mut tree = DT::init();
+        tree.append("1", "data1", 1, Op::Equal)
+            .append("2", "data2", 2, Op::Equal);
+
+        let travel = Traverse::start(tree);
+        assert!(travel.fork().traverse_with(&2, &Op::Greater).unwrap().id() == "1");
+        assert!(travel.fork().traverse_with(&2, &Op::Default).is_none());
+        assert!(
+            travel
+                .fork()
+                .traverse_with(&1, &PartialOp::Less)
+                .unwrap()
+                .id()
+                == "2"
+        );
+        assert!(travel.fork().traverse_with(&1, &PartialOp::Max).is_none());
+        let mut travel = travel.fork();
+        travel.traverse_with(&2, &Op::NotEqual);
+        assert!(travel.op_usage().get("NotEqual") == Some(&1));
     }
 }